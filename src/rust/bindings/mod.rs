@@ -0,0 +1,398 @@
+use std::io;
+use std::path::PathBuf;
+
+use piston::input::{Button, ControllerAxisEvent, ControllerButton, Input, Key, PressEvent,
+                    ReleaseEvent};
+
+use config;
+
+/// File in the config dir which key bindings are saved to.
+const BINDINGS_FILE: &'static str = "bindings.conf";
+/// Prefix for controller settings in the bindings file, such as `controller.jump: 0`.
+const CONTROLLER_PREFIX: &'static str = "controller.";
+
+/// Something a key can be bound to, used instead of checking for keys directly.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    MenuUp,
+    MenuDown,
+    Confirm,
+    Back,
+    Pause,
+    Restart,
+}
+
+pub const ACTIONS: [Action; 9] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Jump,
+    Action::MenuUp,
+    Action::MenuDown,
+    Action::Confirm,
+    Action::Back,
+    Action::Pause,
+    Action::Restart,
+];
+
+impl Action {
+    /// The name used in the bindings file.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Jump => "jump",
+            Action::MenuUp => "menu_up",
+            Action::MenuDown => "menu_down",
+            Action::Confirm => "confirm",
+            Action::Back => "back",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().cloned().find(|action| action.name() == name)
+    }
+}
+
+/// The name of a key, as written in the bindings file and shown in menus.
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    // Key doesn't have a way to be parsed, so this looks through the ranges of codes keys use.
+    let name = name.to_lowercase();
+    (0u32..0x80).chain(0x4000_0039..0x4000_0120)
+        .map(Key::from)
+        .filter(|&key| key != Key::Unknown)
+        .find(|&key| key_name(key).to_lowercase() == name)
+}
+
+/// Which keys and controller buttons do which actions. Each action can have any number of keys,
+/// and one key can do several actions, such as up both jumping and moving up in menus.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    keys: Vec<(Action, Key)>,
+    /// Controller buttons, by number.
+    buttons: Vec<(Action, u8)>,
+    /// The stick axis which moves left and right.
+    axis_x: u8,
+    /// The stick axis which moves up and down in menus, with down being positive.
+    axis_y: u8,
+    /// How far, from 0 to 1, a stick has to be pushed before it counts.
+    deadzone: f64,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: vec![
+                (Action::MoveLeft, Key::Left),
+                (Action::MoveRight, Key::Right),
+                (Action::Jump, Key::Up),
+                (Action::MenuUp, Key::Up),
+                (Action::MenuDown, Key::Down),
+                (Action::Confirm, Key::Return),
+                (Action::Back, Key::Escape),
+                (Action::Pause, Key::P),
+                (Action::Restart, Key::R),
+            ],
+            // Numbered the way SDL numbers a standard gamepad's buttons.
+            buttons: vec![
+                (Action::MoveLeft, 13),
+                (Action::MoveRight, 14),
+                (Action::Jump, 0),
+                (Action::MenuUp, 11),
+                (Action::MenuDown, 12),
+                (Action::Confirm, 0),
+                (Action::Back, 1),
+                (Action::Pause, 6),
+                (Action::Restart, 3),
+            ],
+            axis_x: 0,
+            axis_y: 1,
+            deadzone: 0.3,
+        }
+    }
+}
+
+impl Bindings {
+    /// Actions done by pressing or releasing the button.
+    pub fn actions(&self, button: &Button) -> Vec<Action> {
+        match *button {
+            Button::Keyboard(pressed) => {
+                self.keys.iter()
+                    .filter(|&&(_, key)| key == pressed)
+                    .map(|&(action, _)| action)
+                    .collect()
+            }
+            Button::Controller(ControllerButton { button: pressed, .. }) => {
+                self.buttons.iter()
+                    .filter(|&&(_, button)| button == pressed)
+                    .map(|&(action, _)| action)
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        self.keys.iter()
+            .filter(|&&(bound, _)| bound == action)
+            .map(|&(_, key)| key)
+            .collect()
+    }
+
+    /// Adds another key for the action.
+    pub fn bind(&mut self, action: Action, key: Key) {
+        if !self.keys.contains(&(action, key)) {
+            self.keys.push((action, key));
+        }
+    }
+
+    /// Removes the key most recently bound to the action, unless it's the only one left.
+    pub fn unbind_last(&mut self, action: Action) {
+        if self.keys(action).len() > 1 {
+            if let Some(index) = self.keys.iter().rposition(|&(bound, _)| bound == action) {
+                self.keys.remove(index);
+            }
+        }
+    }
+
+    /// -1, 0 or 1 for which way a stick axis at `position` is pushed, past the deadzone.
+    pub fn stick_direction(&self, position: f64) -> i32 {
+        if position <= -self.deadzone {
+            -1
+        } else if position >= self.deadzone {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Sets one `controller.*` setting from the bindings file.
+    fn load_controller(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value for {}{}: {}", CONTROLLER_PREFIX, name, value);
+        match name {
+            "axis_x" => self.axis_x = try!(value.parse().map_err(|_| invalid())),
+            "axis_y" => self.axis_y = try!(value.parse().map_err(|_| invalid())),
+            "deadzone" => self.deadzone = try!(value.parse().map_err(|_| invalid())),
+            _ => {
+                let action = try!(Action::from_name(name)
+                    .ok_or_else(|| format!("unknown action: {}{}", CONTROLLER_PREFIX, name)));
+                let buttons = try!(value.split(',')
+                    .map(str::trim)
+                    .filter(|button| !button.is_empty())
+                    .map(|button| button.parse::<u8>().map_err(|_| invalid()))
+                    .collect::<Result<Vec<_>, _>>());
+                self.buttons.retain(|&(bound, _)| bound != action);
+                self.buttons.extend(buttons.into_iter().map(|button| (action, button)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the saved bindings. Actions not in the file keep their default keys.
+    pub fn load() -> Bindings {
+        let mut bindings = Bindings::default();
+        let text = match config::read(BINDINGS_FILE) {
+            Ok(Some(text)) => text,
+            Ok(None) => return bindings,
+            Err(e) => {
+                println!("Failed to read {}, using default bindings: {}", BINDINGS_FILE, e);
+                return bindings;
+            }
+        };
+        let pairs = match config::parse(&text) {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                println!("Failed to load {}, using default bindings: {}", BINDINGS_FILE, e);
+                return bindings;
+            }
+        };
+        for (name, keys) in pairs {
+            if name.starts_with(CONTROLLER_PREFIX) {
+                if let Err(e) = bindings.load_controller(&name[CONTROLLER_PREFIX.len()..], keys) {
+                    println!("Ignoring setting in {}: {}", BINDINGS_FILE, e);
+                }
+                continue;
+            }
+            let action = match Action::from_name(name) {
+                Some(action) => action,
+                None => {
+                    println!("Ignoring unknown action in {}: {}", BINDINGS_FILE, name);
+                    continue;
+                }
+            };
+            let keys = keys.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| {
+                    let key = key_from_name(name);
+                    if key.is_none() {
+                        println!("Ignoring unknown key in {}: {}", BINDINGS_FILE, name);
+                    }
+                    key
+                })
+                .collect::<Vec<_>>();
+            if keys.is_empty() {
+                continue;
+            }
+            bindings.keys.retain(|&(bound, _)| bound != action);
+            for key in keys {
+                bindings.bind(action, key);
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let mut text = ACTIONS.iter().map(|&action| {
+            let keys = self.keys(action).into_iter().map(key_name).collect::<Vec<_>>();
+            format!("{}: {}\n", action.name(), keys.join(", "))
+        }).collect::<String>();
+        for &action in &ACTIONS {
+            let buttons = self.buttons.iter()
+                .filter(|&&(bound, _)| bound == action)
+                .map(|&(_, button)| button.to_string())
+                .collect::<Vec<_>>();
+            text.push_str(&format!("{}{}: {}\n", CONTROLLER_PREFIX, action.name(),
+                                   buttons.join(", ")));
+        }
+        text.push_str(&format!("{}axis_x: {}\n", CONTROLLER_PREFIX, self.axis_x));
+        text.push_str(&format!("{}axis_y: {}\n", CONTROLLER_PREFIX, self.axis_y));
+        text.push_str(&format!("{}deadzone: {}\n", CONTROLLER_PREFIX, self.deadzone));
+        config::write(BINDINGS_FILE, &text)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionEvent {
+    Pressed(Action),
+    Released(Action),
+}
+
+/// Turns input into actions being pressed and released. Sticks are tracked so that pushing one
+/// past the deadzone presses an action, and letting it back releases it, like a button.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ActionTracker {
+    /// Which way, -1, 0 or 1, each stick axis was last pushed.
+    stick_x: i32,
+    stick_y: i32,
+}
+
+impl ActionTracker {
+    pub fn events(&mut self, bindings: &Bindings, input: &Input) -> Vec<ActionEvent> {
+        let mut events = Vec::new();
+        if let Some(button) = input.press_args() {
+            events.extend(bindings.actions(&button).into_iter().map(ActionEvent::Pressed));
+        }
+        if let Some(button) = input.release_args() {
+            events.extend(bindings.actions(&button).into_iter().map(ActionEvent::Released));
+        }
+        if let Some(args) = input.controller_axis_args() {
+            let direction = bindings.stick_direction(args.position);
+            if args.axis == bindings.axis_x {
+                stick_events(&mut self.stick_x, direction, Action::MoveLeft, Action::MoveRight,
+                             &mut events);
+            } else if args.axis == bindings.axis_y {
+                stick_events(&mut self.stick_y, direction, Action::MenuUp, Action::MenuDown,
+                             &mut events);
+            }
+        }
+        events
+    }
+
+    /// Just the actions pressed by the input.
+    pub fn pressed(&mut self, bindings: &Bindings, input: &Input) -> Vec<Action> {
+        self.events(bindings, input).into_iter().filter_map(|event| {
+            match event {
+                ActionEvent::Pressed(action) => Some(action),
+                ActionEvent::Released(_) => None,
+            }
+        }).collect()
+    }
+}
+
+/// Releases the action for the way the stick was pushed, and presses the one for the way it's
+/// pushed now, if that changed.
+fn stick_events(held: &mut i32, direction: i32, negative: Action, positive: Action,
+                events: &mut Vec<ActionEvent>) {
+    if *held == direction {
+        return;
+    }
+    match *held {
+        -1 => events.push(ActionEvent::Released(negative)),
+        1 => events.push(ActionEvent::Released(positive)),
+        _ => (),
+    }
+    match direction {
+        -1 => events.push(ActionEvent::Pressed(negative)),
+        1 => events.push(ActionEvent::Pressed(positive)),
+        _ => (),
+    }
+    *held = direction;
+}
+
+#[cfg(test)]
+mod tests {
+    use piston::input::{Button, ControllerAxisArgs, ControllerButton, Input, Motion};
+
+    use super::{Action, ActionEvent, ActionTracker, Bindings};
+
+    fn stick(axis: u8, position: f64) -> Input {
+        Input::Move(Motion::ControllerAxis(ControllerAxisArgs {
+            id: 0,
+            axis: axis,
+            position: position,
+        }))
+    }
+
+    fn button(button: u8) -> Button {
+        Button::Controller(ControllerButton { id: 0, button: button })
+    }
+
+    #[test]
+    fn stick_presses_and_releases_past_deadzone() {
+        let bindings = Bindings::default();
+        let mut tracker = ActionTracker::default();
+        assert_eq!(tracker.events(&bindings, &stick(0, 0.2)), vec![]);
+        assert_eq!(tracker.events(&bindings, &stick(0, 0.5)),
+                   vec![ActionEvent::Pressed(Action::MoveRight)]);
+        assert_eq!(tracker.events(&bindings, &stick(0, 0.1)),
+                   vec![ActionEvent::Released(Action::MoveRight)]);
+        assert_eq!(tracker.events(&bindings, &stick(1, -0.8)),
+                   vec![ActionEvent::Pressed(Action::MenuUp)]);
+    }
+
+    #[test]
+    fn holding_stick_does_not_repeat() {
+        let bindings = Bindings::default();
+        let mut tracker = ActionTracker::default();
+        assert_eq!(tracker.events(&bindings, &stick(0, -0.5)),
+                   vec![ActionEvent::Pressed(Action::MoveLeft)]);
+        assert_eq!(tracker.events(&bindings, &stick(0, -0.7)), vec![]);
+        assert_eq!(tracker.events(&bindings, &stick(0, -1.0)), vec![]);
+        // Flicking straight across releases one side before pressing the other.
+        assert_eq!(tracker.events(&bindings, &stick(0, 1.0)),
+                   vec![ActionEvent::Released(Action::MoveLeft),
+                        ActionEvent::Pressed(Action::MoveRight)]);
+    }
+
+    #[test]
+    fn buttons_map_to_actions() {
+        let bindings = Bindings::default();
+        let mut tracker = ActionTracker::default();
+        assert_eq!(tracker.pressed(&bindings, &Input::Press(button(0))),
+                   vec![Action::Jump, Action::Confirm]);
+        assert_eq!(tracker.pressed(&bindings, &Input::Press(button(1))), vec![Action::Back]);
+        assert_eq!(tracker.events(&bindings, &Input::Release(button(0))),
+                   vec![ActionEvent::Released(Action::Jump),
+                        ActionEvent::Released(Action::Confirm)]);
+        assert_eq!(tracker.events(&bindings, &Input::Press(button(99))), vec![]);
+    }
+}
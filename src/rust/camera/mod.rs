@@ -0,0 +1,255 @@
+use std::f64;
+
+/// Tuning for how the camera follows its target.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraSettings {
+    /// How quickly the camera eases towards where it wants to be. Higher is stiffer.
+    pub follow_speed: f64,
+    /// Seconds of the target's horizontal velocity to look ahead by.
+    pub look_ahead_time: f64,
+    pub max_look_ahead: f64,
+    /// (width, height) of the box around the view center the target can move within without
+    /// the camera following it.
+    pub dead_zone: (f64, f64),
+    /// When set, the camera doesn't move vertically while the target is grounded, so walking
+    /// over small bumps doesn't shake the view. It follows again once the target leaves the
+    /// ground.
+    pub vertical_lock: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            follow_speed: 6.0,
+            look_ahead_time: 0.3,
+            max_look_ahead: 80.0,
+            dead_zone: (40.0, 60.0),
+            vertical_lock: true,
+        }
+    }
+}
+
+/// What the camera is following, in world coordinates.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CameraTarget {
+    /// center of the target
+    pub x: f64,
+    pub y: f64,
+    pub velocity_x: f64,
+    pub grounded: bool,
+}
+
+pub struct Camera {
+    /// center of the view
+    x: f64,
+    y: f64,
+    pub settings: CameraSettings,
+    screen_width: f64,
+    screen_height: f64,
+    look_ahead: f64,
+    /// Height the camera is trying to center on, see `CameraSettings::vertical_lock`.
+    focus_y: f64,
+    /// Jump straight to the target on the next update rather than easing. Kept until the screen
+    /// size is known, since the view can't be placed before then.
+    snap: bool,
+}
+
+impl Camera {
+    pub fn new(x: f64, y: f64) -> Camera {
+        Camera {
+            x: x,
+            y: y,
+            settings: CameraSettings::default(),
+            screen_width: 0.0,
+            screen_height: 0.0,
+            look_ahead: 0.0,
+            focus_y: y,
+            snap: true,
+        }
+    }
+
+    pub fn resize(&mut self, screen_width: f64, screen_height: f64) {
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+    }
+
+    /// Makes the next update jump straight to the target, for respawns and level changes.
+    pub fn reset(&mut self) {
+        self.snap = true;
+    }
+
+    /// Gives the (x, y) world coordinates of the center of the view, rounded to whole pixels.
+    pub fn scroll(&self) -> (f64, f64) {
+        (self.x.floor(), self.y.floor())
+    }
+
+    /// Moves the camera towards `target`. `bounds` is the level boundary and `zones` are
+    /// level-defined areas which replace it while the target is inside of them, all given as
+    /// `[x, y, width, height]`.
+    pub fn update(&mut self, dt: f64, target: &CameraTarget, bounds: [f64; 4],
+                  zones: &[[f64; 4]]) {
+        let half_width = self.screen_width / 2.0;
+        let half_height = self.screen_height / 2.0;
+        let ease = if self.snap {
+            1.0
+        } else {
+            1.0 - (-self.settings.follow_speed * dt).exp()
+        };
+
+        let look_ahead = f64::min(f64::max(target.velocity_x * self.settings.look_ahead_time,
+                                           -self.settings.max_look_ahead),
+                                  self.settings.max_look_ahead);
+        self.look_ahead += (look_ahead - self.look_ahead) * ease;
+
+        if self.snap || !self.settings.vertical_lock || !target.grounded {
+            self.focus_y = target.y;
+        }
+
+        let (goal_x, goal_y) = if self.snap {
+            (target.x + self.look_ahead, self.focus_y)
+        } else {
+            (outside_dead_zone(self.x, target.x + self.look_ahead, self.settings.dead_zone.0),
+             outside_dead_zone(self.y, self.focus_y, self.settings.dead_zone.1))
+        };
+
+        // Whatever the easing does, never let the target get near the edge of the screen.
+        let x = clamp(self.x + (goal_x - self.x) * ease,
+                      target.x - half_width * 0.75,
+                      target.x + half_width * 0.75);
+        let y = clamp(self.y + (goal_y - self.y) * ease,
+                      target.y - half_height * 0.75,
+                      target.y + half_height * 0.75);
+
+        let area = zones.iter()
+            .find(|zone| contains(zone, target.x, target.y))
+            .cloned()
+            .unwrap_or(bounds);
+
+        self.x = clamp_to_area(x, half_width, area[0], area[2]);
+        self.y = clamp_to_area(y, half_height, area[1], area[3]);
+        if self.screen_width > 0.0 && self.screen_height > 0.0 {
+            self.snap = false;
+        }
+    }
+}
+
+/// Gives the closest position to `current` which has `desired` inside a dead zone of `size`.
+fn outside_dead_zone(current: f64, desired: f64, size: f64) -> f64 {
+    let half_size = size / 2.0;
+    if desired > current + half_size {
+        desired - half_size
+    } else if desired < current - half_size {
+        desired + half_size
+    } else {
+        current
+    }
+}
+
+fn clamp(value: f64, min: f64, max: f64) -> f64 {
+    f64::min(f64::max(value, min), max)
+}
+
+/// Clamps a view center so the view stays inside the area, or centers it if the area is
+/// smaller than the view.
+fn clamp_to_area(center: f64, half_view: f64, area_start: f64, area_length: f64) -> f64 {
+    if half_view * 2.0 >= area_length {
+        area_start + area_length / 2.0
+    } else {
+        clamp(center, area_start + half_view, area_start + area_length - half_view)
+    }
+}
+
+fn contains(area: &[f64; 4], x: f64, y: f64) -> bool {
+    x >= area[0] && x <= area[0] + area[2] && y >= area[1] && y <= area[1] + area[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Camera, CameraTarget};
+
+    const BOUNDS: [f64; 4] = [-1000.0, -1000.0, 2000.0, 2000.0];
+    /// Long enough that easing finishes within one update.
+    const LONG_DT: f64 = 10.0;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(0.0, 0.0);
+        camera.settings.dead_zone = (0.0, 0.0);
+        camera.resize(100.0, 100.0);
+        camera
+    }
+
+    fn target(x: f64, y: f64) -> CameraTarget {
+        CameraTarget { x: x, y: y, velocity_x: 0.0, grounded: false }
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 0.01, "expected {}, found {}", expected, actual);
+    }
+
+    #[test]
+    fn stays_still_inside_dead_zone() {
+        let mut camera = camera();
+        camera.settings.dead_zone = (40.0, 40.0);
+        camera.update(LONG_DT, &target(0.0, 0.0), BOUNDS, &[]);
+        camera.update(LONG_DT, &target(15.0, 0.0), BOUNDS, &[]);
+        assert_near(camera.x, 0.0);
+
+        camera.update(LONG_DT, &target(50.0, 0.0), BOUNDS, &[]);
+        assert_near(camera.x, 30.0);
+    }
+
+    #[test]
+    fn looks_ahead_in_movement_direction() {
+        let mut camera = camera();
+        let moving = CameraTarget { velocity_x: 100.0, ..target(0.0, 0.0) };
+        camera.update(LONG_DT, &moving, BOUNDS, &[]);
+        assert_near(camera.x, 100.0 * camera.settings.look_ahead_time);
+
+        let moving_left = CameraTarget { velocity_x: -1000.0, ..target(0.0, 0.0) };
+        camera.update(LONG_DT, &moving_left, BOUNDS, &[]);
+        // Limited by max_look_ahead, then by keeping the target on screen.
+        assert_near(camera.x, -37.5);
+    }
+
+    #[test]
+    fn clamps_to_level_bounds() {
+        let mut camera = camera();
+        let bounds = [-100.0, -100.0, 200.0, 200.0];
+        camera.update(LONG_DT, &target(90.0, -90.0), bounds, &[]);
+        assert_eq!(camera.scroll(), (50.0, -50.0));
+    }
+
+    #[test]
+    fn uses_zone_containing_target() {
+        let mut camera = camera();
+        let zones = [[200.0, 0.0, 100.0, 100.0], [500.0, 0.0, 300.0, 300.0]];
+        camera.update(LONG_DT, &target(210.0, 10.0), BOUNDS, &zones);
+        // The zone is exactly one screen, so the view is centered on it.
+        assert_eq!(camera.scroll(), (250.0, 50.0));
+
+        camera.reset();
+        camera.update(LONG_DT, &target(-500.0, 10.0), BOUNDS, &zones);
+        assert_eq!(camera.scroll(), (-500.0, 10.0));
+    }
+
+    #[test]
+    fn vertical_lock_holds_while_grounded() {
+        let mut camera = camera();
+        let grounded = CameraTarget { grounded: true, ..target(0.0, 0.0) };
+        camera.update(LONG_DT, &grounded, BOUNDS, &[]);
+        camera.update(LONG_DT, &CameraTarget { y: 10.0, ..grounded }, BOUNDS, &[]);
+        assert_near(camera.y, 0.0);
+
+        camera.update(LONG_DT, &target(0.0, 20.0), BOUNDS, &[]);
+        assert_near(camera.y, 20.0);
+    }
+
+    #[test]
+    fn snap_waits_for_screen_size() {
+        let mut camera = Camera::new(0.0, 0.0);
+        camera.update(0.01, &target(0.0, 0.0), BOUNDS, &[]);
+        camera.resize(100.0, 100.0);
+        camera.update(0.01, &target(30.0, 20.0), BOUNDS, &[]);
+        assert_eq!(camera.scroll(), (30.0, 20.0));
+    }
+}
@@ -0,0 +1,63 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+/// `$XDG_CONFIG_HOME/bwadvent`, or `$HOME/.config/bwadvent` if that isn't set.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match (non_empty_var("XDG_CONFIG_HOME"), non_empty_var("HOME")) {
+        (Some(dir), _) => Some(PathBuf::from(dir)),
+        (None, Some(home)) => Some(PathBuf::from(home).join(".config")),
+        (None, None) => None,
+    };
+    base.map(|dir| dir.join("bwadvent"))
+}
+
+fn non_empty_var(name: &str) -> Option<OsString> {
+    env::var_os(name).and_then(|value| if value.is_empty() { None } else { Some(value) })
+}
+
+fn no_config_dir() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "couldn't find a config directory")
+}
+
+/// Reads a file in the config dir, giving None if it doesn't exist yet.
+pub fn read(name: &str) -> io::Result<Option<String>> {
+    let path = try!(config_dir().ok_or_else(no_config_dir)).join(name);
+    let mut text = String::new();
+    match File::open(&path) {
+        Ok(mut file) => {
+            try!(file.read_to_string(&mut text));
+            Ok(Some(text))
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces a file in the config dir, creating the dir if needed. Gives the file's full path.
+pub fn write(name: &str, contents: &str) -> io::Result<PathBuf> {
+    let dir = try!(config_dir().ok_or_else(no_config_dir));
+    try!(fs::create_dir_all(&dir));
+    let path = dir.join(name);
+    let mut file = try!(File::create(&path));
+    try!(file.write_all(contents.as_bytes()));
+    Ok(path)
+}
+
+/// Splits `key: value` lines, skipping blank lines and `#` comments.
+pub fn parse(text: &str) -> Result<Vec<(&str, &str)>, String> {
+    let mut pairs = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.find(':') {
+            Some(pos) => pairs.push((line[..pos].trim(), line[pos + 1..].trim())),
+            None => return Err(format!("line {}: expected `key: value`", index + 1)),
+        }
+    }
+    Ok(pairs)
+}
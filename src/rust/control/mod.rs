@@ -0,0 +1,160 @@
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use mechanics::SettingsUpdate;
+use settings::Telemetry;
+
+/// Environment variable holding the address to listen for control connections on, such as
+/// `127.0.0.1:7878`. Nothing is listened on if it isn't set.
+const ADDRESS_VAR: &'static str = "BWADVENT_CONTROL";
+/// Seconds a connection waits for the game to answer before giving up.
+const REPLY_TIMEOUT: u64 = 1;
+
+/// A command which has to be answered by the level being played.
+#[derive(Debug, Clone)]
+pub enum Command {
+    GetState,
+    LoadLevel(String),
+}
+
+pub struct Request {
+    pub command: Command,
+    reply: mpsc::Sender<String>,
+}
+
+impl Request {
+    /// Answers with `ok`, followed by the text if there is any, or `error: ` and the message.
+    pub fn reply(&self, result: Result<String, String>) {
+        let line = match result {
+            Ok(ref text) if text.is_empty() => "ok".to_owned(),
+            Ok(text) => format!("ok {}", text),
+            Err(e) => format!("error: {}", e),
+        };
+        // The connection may have stopped waiting already.
+        let _ = self.reply.send(line);
+    }
+}
+
+/// A parsed line from a connection.
+enum Line {
+    /// Handled by the connection itself, the same way the settings window does.
+    Set(SettingsUpdate),
+    Game(Command),
+}
+
+fn parse(line: &str) -> Result<Line, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match (words[0], words.len()) {
+        ("set", 3) => {
+            let value = try!(words[2].parse::<f64>()
+                .map_err(|_| format!("invalid value: {}", words[2])));
+            SettingsUpdate::from_name(words[1], value)
+                .map(Line::Set)
+                .ok_or_else(|| format!("unknown setting: {}", words[1]))
+        }
+        ("get", 2) if words[1] == "state" => Ok(Line::Game(Command::GetState)),
+        ("load", 3) if words[1] == "level" => {
+            Ok(Line::Game(Command::LoadLevel(words[2].to_owned())))
+        }
+        _ => Err(format!("unknown command: {}", line)),
+    }
+}
+
+/// Starts listening if `BWADVENT_CONTROL` is set to a localhost address, giving the requests
+/// which need to be answered by the game.
+pub fn start_from_env(settings: mpsc::Sender<SettingsUpdate>, telemetry: mpsc::Sender<Telemetry>)
+                      -> Option<mpsc::Receiver<Request>> {
+    let address = match env::var(ADDRESS_VAR) {
+        Ok(address) => address,
+        Err(_) => return None,
+    };
+    let address = match address.parse::<SocketAddr>() {
+        Ok(parsed) if parsed.ip().is_loopback() => parsed,
+        Ok(_) => {
+            println!("{} has to be a localhost address, not {}", ADDRESS_VAR, address);
+            return None;
+        }
+        Err(e) => {
+            println!("Invalid {} address {}: {}", ADDRESS_VAR, address, e);
+            return None;
+        }
+    };
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Failed to listen for control connections on {}: {}", address, e);
+            return None;
+        }
+    };
+    println!("Listening for control connections on {}", address);
+
+    let (request_send, request_recv) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let settings = settings.clone();
+                    let telemetry = telemetry.clone();
+                    let requests = request_send.clone();
+                    thread::spawn(move || handle(stream, settings, telemetry, requests));
+                }
+                Err(e) => println!("Failed to accept control connection: {}", e),
+            }
+        }
+    });
+    Some(request_recv)
+}
+
+/// Answers each line from the connection with a line starting with `ok` or `error:`.
+fn handle(stream: TcpStream, settings: mpsc::Sender<SettingsUpdate>,
+          telemetry: mpsc::Sender<Telemetry>, requests: mpsc::Sender<Request>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            println!("Failed to set up control connection: {}", e);
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let response = match parse(line) {
+            Ok(Line::Set(update)) => {
+                if settings.send(update).is_ok() {
+                    // Keeps the settings window's sliders in step.
+                    let _ = telemetry.send(Telemetry::Setting(update));
+                    "ok".to_owned()
+                } else {
+                    "error: the game has stopped".to_owned()
+                }
+            }
+            Ok(Line::Game(command)) => {
+                let (reply_send, reply_recv) = mpsc::channel();
+                let request = Request {
+                    command: command,
+                    reply: reply_send,
+                };
+                if requests.send(request).is_ok() {
+                    reply_recv.recv_timeout(Duration::from_secs(REPLY_TIMEOUT))
+                        .unwrap_or_else(|_| "error: no level is being played".to_owned())
+                } else {
+                    "error: the game has stopped".to_owned()
+                }
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use piston::input::UpdateArgs;
+use graphics::{self, Context, Transformed};
+
+use {Graphics, GraphicsCache};
+use map::Map;
+use mechanics::PlayerState;
+
+use super::Entity;
+
+/// The area the player needs to reach to complete a level.
+///
+/// `entity.goal: x,y,width,height`
+pub struct Goal {
+    bounds: [f64; 4],
+}
+
+impl Goal {
+    pub fn from_args(args: &[f64]) -> Result<Goal, String> {
+        match args.len() {
+            4 => Ok(Goal { bounds: [args[0], args[1], args[2], args[3]] }),
+            n => Err(format!("goal takes 4 arguments, found {}", n)),
+        }
+    }
+}
+
+impl Entity for Goal {
+    fn update(&mut self, _: &UpdateArgs, _: &Map) {}
+
+    fn bounds(&self) -> [f64; 4] {
+        self.bounds
+    }
+
+    fn render(&self, _: &GraphicsCache, context: &Context, graphics: &mut Graphics) {
+        graphics::Rectangle::new_border(graphics::color::BLACK, 1.0).draw(
+            self.bounds,
+            &context.draw_state,
+            context.flip_v().transform,
+            graphics,
+        );
+    }
+
+    fn on_player_contact(&mut self, player: &mut PlayerState) {
+        if !player.is_dead() {
+            player.reached_goal = true;
+        }
+    }
+}
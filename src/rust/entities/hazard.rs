@@ -0,0 +1,61 @@
+use piston::input::UpdateArgs;
+use graphics::{self, Context, Transformed};
+
+use {Graphics, GraphicsCache};
+use map::Map;
+use mechanics::{DamageSource, PlayerState};
+
+use super::Entity;
+
+/// A stationary box which hurts the player on contact.
+///
+/// `entity.hazard: x,y,width,height[,damage]`
+pub struct Hazard {
+    bounds: [f64; 4],
+    damage: u32,
+}
+
+impl Hazard {
+    pub fn from_args(args: &[f64]) -> Result<Hazard, String> {
+        match args.len() {
+            4 | 5 => {
+                Ok(Hazard {
+                    bounds: [args[0], args[1], args[2], args[3]],
+                    damage: args.get(4).map(|&d| d as u32).unwrap_or(1),
+                })
+            }
+            n => Err(format!("hazard takes 4 or 5 arguments, found {}", n)),
+        }
+    }
+}
+
+impl DamageSource for Hazard {
+    fn damage(&self) -> u32 {
+        self.damage
+    }
+
+    fn origin(&self) -> (f64, f64) {
+        (self.bounds[0] + self.bounds[2] / 2.0, self.bounds[1] + self.bounds[3] / 2.0)
+    }
+}
+
+impl Entity for Hazard {
+    fn update(&mut self, _: &UpdateArgs, _: &Map) {}
+
+    fn bounds(&self) -> [f64; 4] {
+        self.bounds
+    }
+
+    fn render(&self, _: &GraphicsCache, context: &Context, graphics: &mut Graphics) {
+        graphics::Rectangle::new(graphics::color::grey(0.4)).draw(
+            self.bounds,
+            &context.draw_state,
+            context.flip_v().transform,
+            graphics,
+        );
+    }
+
+    fn on_player_contact(&mut self, player: &mut PlayerState) {
+        player.hurt(&*self);
+    }
+}
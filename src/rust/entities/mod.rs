@@ -0,0 +1,60 @@
+mod goal;
+mod hazard;
+mod patroller;
+
+use piston::input::UpdateArgs;
+use graphics::Context;
+
+use {Graphics, GraphicsCache, Hitboxes};
+use level_serialization::EntityDef;
+use map::Map;
+use mechanics::PlayerState;
+
+pub use self::goal::Goal;
+pub use self::hazard::Hazard;
+pub use self::patroller::{Patroller, PatrollerGraphics};
+
+/// Anything in a level which isn't part of the map: hazards, enemies, goals.
+pub trait Entity {
+    fn update(&mut self, args: &UpdateArgs, map: &Map);
+
+    /// [x, y, width, height]
+    fn bounds(&self) -> [f64; 4];
+
+    /// `context` has its origin at world (0, 0), with y pointing down the screen.
+    fn render(&self, cache: &GraphicsCache, context: &Context, graphics: &mut Graphics);
+
+    /// Called each tick the player overlaps this entity's bounds.
+    fn on_player_contact(&mut self, player: &mut PlayerState);
+
+    /// Entities returning true are dropped at the end of the tick.
+    fn is_removed(&self) -> bool {
+        false
+    }
+}
+
+/// Creates an entity from an `entity.<kind>:` line in a level. Entities with sprites are sized
+/// to fit them.
+pub fn spawn(def: &EntityDef, hitboxes: Hitboxes) -> Result<Box<Entity>, String> {
+    match &def.kind[..] {
+        "goal" => Goal::from_args(&def.args).map(|e| Box::new(e) as Box<Entity>),
+        "hazard" => Hazard::from_args(&def.args).map(|e| Box::new(e) as Box<Entity>),
+        "patroller" => {
+            Patroller::from_args(&def.args, hitboxes.patroller)
+                .map(|e| Box::new(e) as Box<Entity>)
+        }
+        other => Err(format!("unknown entity kind `{}`", other)),
+    }
+}
+
+/// Whether two `[x, y, width, height]` rectangles overlap.
+pub fn overlaps(a: [f64; 4], b: [f64; 4]) -> bool {
+    a[0] < b[0] + b[2] && a[0] + a[2] > b[0] && a[1] < b[1] + b[3] && a[1] + a[3] > b[1]
+}
+
+/// Whether any map block or boundary overlaps the `[x, y, width, height]` rectangle.
+pub fn solid_at(map: &Map, area: [f64; 4]) -> bool {
+    map.blocks().iter().chain(map.boundary_collision_lines().iter()).any(|block| {
+        overlaps(block.into(), area)
+    })
+}
@@ -0,0 +1,340 @@
+use piston::input::UpdateArgs;
+use graphics::{self, Context, ImageSize, Transformed};
+use graphics::types::Color;
+
+use collisions::{self, HasBounds};
+
+use {Graphics, GraphicsCache};
+use map::Map;
+use mechanics::{DamageSource, PlayerState};
+use sprites::{self, Animation, SpriteError, SpriteSheet};
+
+use super::{Entity, solid_at};
+
+pub const PATROLLER_DEFAULT_SPEED: f64 = 40.0;
+pub const PATROLLER_GRAVITY: f64 = 600.0;
+/// Multiplied with the player sprites so patrollers stand out.
+pub const PATROLLER_TINT: Color = [1.0, 0.35, 0.35, 1.0];
+/// How far into the top of a patroller the player can be and still count as landing on it.
+pub const STOMP_MARGIN: f64 = 6.0;
+/// Upwards velocity given to the player after landing on a patroller.
+pub const STOMP_BOUNCE: f64 = 250.0;
+/// Seconds the attack animation is shown for after hurting the player.
+pub const ATTACK_DURATION: f64 = 0.5;
+/// Seconds a defeated patroller lies on the ground before disappearing.
+pub const DEFEATED_DURATION: f64 = 1.0;
+
+/// Animations `patroller.manifest` has, one for each field of `PatrollerGraphics`.
+const PATROLLER_ANIMATIONS: &'static [&'static str] = &[
+    "advance_left",
+    "advance_right",
+    "attack_left",
+    "attack_right",
+    "fallen_left",
+    "fallen_right",
+];
+
+pub struct PatrollerGraphics {
+    advance_left: Animation,
+    advance_right: Animation,
+    attack_left: Animation,
+    attack_right: Animation,
+    fallen_left: Animation,
+    fallen_right: Animation,
+    /// width, height
+    collision_box: (u32, u32),
+}
+
+impl PatrollerGraphics {
+    pub fn load() -> PatrollerGraphics {
+        sprites::load("patroller", PATROLLER_ANIMATIONS, PatrollerGraphics::from_sheet)
+    }
+
+    fn from_sheet(mut sheet: SpriteSheet) -> Result<PatrollerGraphics, SpriteError> {
+        Ok(PatrollerGraphics {
+            advance_left: try!(sheet.take("advance_left")),
+            advance_right: try!(sheet.take("advance_right")),
+            attack_left: try!(sheet.take("attack_left")),
+            attack_right: try!(sheet.take("attack_right")),
+            fallen_left: try!(sheet.take("fallen_left")),
+            fallen_right: try!(sheet.take("fallen_right")),
+            collision_box: sheet.collision_box(),
+        })
+    }
+
+    /// width, height
+    pub fn collision_box(&self) -> (u32, u32) {
+        self.collision_box
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PatrollerState {
+    Walking,
+    /// seconds remaining
+    Attacking(f64),
+    Defeated(f64),
+}
+
+/// Walks back and forth along whatever platform it's standing on, turning at walls and edges.
+///
+/// `entity.patroller: x,y[,speed]`
+pub struct Patroller {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    velocity_y: f64,
+    /// 1.0 for east, -1.0 for west
+    direction: f64,
+    speed: f64,
+    grounded: bool,
+    state: PatrollerState,
+    animation_time: f64,
+}
+
+impl Patroller {
+    /// Creates a patroller with the (width, height) hitbox from its sprite manifest.
+    pub fn from_args(args: &[f64], (width, height): (u32, u32)) -> Result<Patroller, String> {
+        match args.len() {
+            2 | 3 => {
+                Ok(Patroller {
+                    x: args[0],
+                    y: args[1],
+                    width: width as f64,
+                    height: height as f64,
+                    velocity_y: 0.0,
+                    direction: 1.0,
+                    speed: args.get(2).cloned().unwrap_or(PATROLLER_DEFAULT_SPEED),
+                    grounded: false,
+                    state: PatrollerState::Walking,
+                    animation_time: 0.0,
+                })
+            }
+            n => Err(format!("patroller takes 2 or 3 arguments, found {}", n)),
+        }
+    }
+}
+
+impl collisions::HasBounds for Patroller {
+    fn min_x(&self) -> f64 {
+        self.x
+    }
+
+    fn min_y(&self) -> f64 {
+        self.y
+    }
+
+    fn len_x(&self) -> f64 {
+        self.width
+    }
+
+    fn len_y(&self) -> f64 {
+        self.height
+    }
+}
+
+impl DamageSource for Patroller {
+    fn damage(&self) -> u32 {
+        1
+    }
+
+    fn origin(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+impl Entity for Patroller {
+    fn update(&mut self, args: &UpdateArgs, map: &Map) {
+        let delta_time = args.dt;
+        self.animation_time += delta_time;
+
+        match self.state {
+            PatrollerState::Defeated(remaining) => {
+                self.state = PatrollerState::Defeated(remaining - delta_time);
+                return;
+            }
+            PatrollerState::Attacking(remaining) => {
+                self.state = if remaining <= delta_time {
+                    PatrollerState::Walking
+                } else {
+                    PatrollerState::Attacking(remaining - delta_time)
+                };
+            }
+            PatrollerState::Walking => (),
+        }
+
+        self.velocity_y -= PATROLLER_GRAVITY * delta_time;
+
+        let mut new_x = self.x;
+        if self.grounded {
+            new_x += self.direction * self.speed * delta_time;
+            // Probe just below the leading foot, turning around rather than walking off the edge.
+            let probe_x = if self.direction > 0.0 { new_x + self.width } else { new_x - 1.0 };
+            if !solid_at(map, [probe_x, self.y - 1.0, 1.0, 1.0]) {
+                self.direction = -self.direction;
+                new_x = self.x;
+            }
+        }
+        let new_y = self.y + self.velocity_y * delta_time;
+
+        let collisions = self.collides(new_x, new_y,
+            map.blocks().iter().chain(map.boundary_collision_lines().iter()));
+
+        match (collisions.east, collisions.west) {
+            (Some(wall), _) if self.direction > 0.0 => {
+                self.x = wall;
+                self.direction = -1.0;
+            }
+            (_, Some(wall)) if self.direction < 0.0 => {
+                self.x = wall;
+                self.direction = 1.0;
+            }
+            _ => self.x = new_x,
+        }
+
+        self.grounded = collisions.south.is_some();
+        match (collisions.south, collisions.north) {
+            (Some(floor), _) if self.velocity_y <= 0.0 => {
+                self.y = floor;
+                self.velocity_y = 0.0;
+            }
+            (_, Some(ceiling)) if self.velocity_y > 0.0 => {
+                self.y = ceiling;
+                self.velocity_y = 0.0;
+            }
+            _ => self.y = new_y,
+        }
+    }
+
+    fn bounds(&self) -> [f64; 4] {
+        [self.x, self.y, self.width, self.height]
+    }
+
+    fn render(&self, cache: &GraphicsCache, context: &Context, graphics: &mut Graphics) {
+        let sprites = &cache.patroller;
+        let facing_left = self.direction < 0.0;
+        let animation = match (self.state, facing_left) {
+            (PatrollerState::Walking, true) => &sprites.advance_left,
+            (PatrollerState::Walking, false) => &sprites.advance_right,
+            (PatrollerState::Attacking(_), true) => &sprites.attack_left,
+            (PatrollerState::Attacking(_), false) => &sprites.attack_right,
+            (PatrollerState::Defeated(_), true) => &sprites.fallen_left,
+            (PatrollerState::Defeated(_), false) => &sprites.fallen_right,
+        };
+        let frame = animation.frame(self.animation_time);
+        graphics::Image::new_color(PATROLLER_TINT).draw(
+            frame.texture,
+            &context.draw_state,
+            context.trans(
+                self.x.ceil() + frame.x_offset,
+                -(self.y.ceil() + frame.texture.get_height() as f64 + frame.y_offset)
+            ).transform,
+            graphics,
+        );
+    }
+
+    fn on_player_contact(&mut self, player: &mut PlayerState) {
+        if let PatrollerState::Defeated(_) = self.state {
+            return;
+        }
+        if player.velocity_y() < 0.0 && player.min_y() >= self.y + self.height - STOMP_MARGIN {
+            self.state = PatrollerState::Defeated(DEFEATED_DURATION);
+            player.bounce(STOMP_BOUNCE);
+        } else if player.hurt(&*self) {
+            self.state = PatrollerState::Attacking(ATTACK_DURATION);
+            let player_center = player.min_x() + player.len_x() / 2.0;
+            self.direction = if player_center < self.x + self.width / 2.0 { -1.0 } else { 1.0 };
+        }
+    }
+
+    fn is_removed(&self) -> bool {
+        match self.state {
+            PatrollerState::Defeated(remaining) => remaining <= 0.0,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use piston::input::UpdateArgs;
+
+    use ::SettingsChannel;
+    use collisions::HasBounds;
+    use level_serialization::{Level, LevelItem};
+    use map::Map;
+    use mechanics::{PLAYER_MAX_HEALTH, PlayerSettings, PlayerState};
+
+    use super::{Patroller, PatrollerState, STOMP_BOUNCE};
+    use super::super::Entity;
+
+    fn settings_channel() -> SettingsChannel {
+        let (send, recv) = mpsc::channel();
+        let (telemetry, _) = mpsc::channel();
+        SettingsChannel::new(send, recv, telemetry, PlayerSettings::default())
+    }
+
+    /// A 100 wide platform with its top at y = 0.
+    fn platform() -> Map {
+        let mut level = Level::empty();
+        level.items = vec![LevelItem::Box { x: 0.0, y: -10.0, width: 100.0, height: 10.0 }];
+        Map::from(&level)
+    }
+
+    fn patroller(x: f64, y: f64) -> Patroller {
+        Patroller::from_args(&[x, y, 40.0], (10, 20)).unwrap()
+    }
+
+    #[test]
+    fn turns_at_ledge() {
+        let map = platform();
+        let mut patroller = patroller(80.0, 0.0);
+        let mut furthest = patroller.x;
+        for _ in 0..100 {
+            patroller.update(&UpdateArgs { dt: 0.02 }, &map);
+            furthest = f64::max(furthest, patroller.x);
+            assert_eq!(patroller.y, 0.0);
+        }
+        // Walked up to the edge, then two seconds at 40 per second back the other way.
+        assert!(furthest <= 90.0, "walked off the edge to {}", furthest);
+        assert!(patroller.direction < 0.0);
+        assert!(patroller.x < 40.0);
+    }
+
+    #[test]
+    fn player_falling_from_above_stomps() {
+        let sc = settings_channel();
+        let mut patroller = patroller(50.0, 0.0);
+        let mut player = PlayerState::new(52.0, patroller.len_y() - 2.0, &sc);
+        // A negative bounce is the player falling.
+        player.bounce(-100.0);
+        patroller.on_player_contact(&mut player);
+
+        match patroller.state {
+            PatrollerState::Defeated(_) => (),
+            other => panic!("expected to be defeated, was {:?}", other),
+        }
+        assert_eq!(player.velocity_y(), STOMP_BOUNCE);
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH);
+    }
+
+    #[test]
+    fn side_contact_hurts_player() {
+        let sc = settings_channel();
+        let mut patroller = patroller(50.0, 0.0);
+        let mut player = PlayerState::new(42.0, 0.0, &sc);
+        patroller.on_player_contact(&mut player);
+
+        match patroller.state {
+            PatrollerState::Attacking(_) => (),
+            other => panic!("expected to be attacking, was {:?}", other),
+        }
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH - 1);
+        // Turned to face the player, who's knocked back the other way.
+        assert!(patroller.direction < 0.0);
+        assert!(player.velocity_x() < 0.0);
+    }
+}
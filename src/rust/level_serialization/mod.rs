@@ -6,7 +6,9 @@ use std::fmt;
 
 use nom;
 
-#[derive(Debug, Clone, Copy)]
+use mechanics::SettingsUpdate;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     North,
     East,
@@ -21,7 +23,7 @@ impl fmt::Display for Direction {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LevelItem {
     Box {
         x: f64,
@@ -37,17 +39,119 @@ pub enum LevelItem {
     },
 }
 
+impl LevelItem {
+    /// [x, y, width, height], with lines being one unit thick.
+    pub fn bounds(&self) -> [f64; 4] {
+        match *self {
+            LevelItem::Box { x, y, width, height } => [x, y, width, height],
+            LevelItem::Line { x, y, direction: Direction::North, length } => [x, y, 1.0, length],
+            LevelItem::Line { x, y, direction: Direction::East, length } => [x, y, length, 1.0],
+        }
+    }
+
+    /// Gives this item moved and resized to fit `[x, y, width, height]`. Lines keep their
+    /// direction and only take the length along it.
+    pub fn with_bounds(&self, bounds: [f64; 4]) -> LevelItem {
+        match *self {
+            LevelItem::Box { .. } => {
+                LevelItem::Box { x: bounds[0], y: bounds[1], width: bounds[2], height: bounds[3] }
+            }
+            LevelItem::Line { direction, .. } => {
+                LevelItem::Line {
+                    x: bounds[0],
+                    y: bounds[1],
+                    direction: direction,
+                    length: match direction {
+                        Direction::North => bounds[3],
+                        Direction::East => bounds[2],
+                    },
+                }
+            }
+        }
+    }
+
+    pub fn translated(&self, dx: f64, dy: f64) -> LevelItem {
+        let bounds = self.bounds();
+        self.with_bounds([bounds[0] + dx, bounds[1] + dy, bounds[2], bounds[3]])
+    }
+}
+
+/// An area the camera is kept within while the player is inside of it.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraZone {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// An `entity.<kind>: args` line, turned into an actual entity by `entities::spawn`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDef {
+    pub kind: String,
+    pub args: Vec<f64>,
+}
+
+impl EntityDef {
+    /// Every kind of entity starts with its x and y.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        if self.args.len() >= 2 {
+            Some((self.args[0], self.args[1]))
+        } else {
+            None
+        }
+    }
+
+    pub fn translated(&self, dx: f64, dy: f64) -> EntityDef {
+        let mut moved = self.clone();
+        if moved.args.len() >= 2 {
+            moved.args[0] += dx;
+            moved.args[1] += dy;
+        }
+        moved
+    }
+}
+
+/// Items and entities on their own, without the rest of a level, such as copied in the editor.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fragment {
+    pub items: Vec<LevelItem>,
+    pub entities: Vec<EntityDef>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Level {
     pub initial_x: f64,
     pub initial_y: f64,
     pub items: Vec<LevelItem>,
+    pub camera_zones: Vec<CameraZone>,
+    pub entities: Vec<EntityDef>,
+    /// Physics settings changed just for this level, from the `physics:` line.
+    pub physics: Vec<SettingsUpdate>,
     pub east_boundary: f64,
     pub south_boundary: f64,
     pub north_boundary: f64,
     pub west_boundary: f64,
 }
 
+impl Level {
+    /// A level with nothing in it but the player start, and default bounds around it.
+    pub fn empty() -> Level {
+        Level {
+            initial_x: 0.0,
+            initial_y: 0.0,
+            items: Vec::new(),
+            camera_zones: Vec::new(),
+            entities: Vec::new(),
+            physics: Vec::new(),
+            west_boundary: -200.0,
+            south_boundary: -200.0,
+            east_boundary: 1000.0,
+            north_boundary: 1000.0,
+        }
+    }
+}
+
 named! {
     level_initial_coords<(f64, f64)>,
 
@@ -93,6 +197,33 @@ named! {
     )
 }
 
+named! {
+    level_physics<Vec<SettingsUpdate>>,
+
+    map_res! (
+        chain! (
+            tag!("physics")~
+            opt!(complete!(call!(nom::space)))~
+            tag!(":")~
+            opt!(complete!(call!(nom::space)))~
+            settings: take_until_and_consume!("\n"),
+            || settings
+        ),
+        |settings| {
+            let settings = try!(str::from_utf8(settings).map_err(|_| ())).trim();
+            if settings.is_empty() {
+                return Ok(Vec::new());
+            }
+            settings.split(',').map(|setting| {
+                let mut parts = setting.splitn(2, '=');
+                let name = parts.next().unwrap_or("").trim();
+                let value = try!(parse_f64(parts.next().unwrap_or("").trim().as_bytes()));
+                SettingsUpdate::from_name(name, value).ok_or(())
+            }).collect::<Result<Vec<_>, ()>>()
+        }
+    )
+}
+
 named! {
     direction<Direction>,
 
@@ -159,6 +290,89 @@ named! {
     alt!(call!(level_item_box) | call!(level_item_line))
 }
 
+named! {
+    level_camera_zone<CameraZone>,
+
+    map_res! (
+        chain! (
+            tag!("camera.zone")~
+            opt!(complete!(call!(nom::space)))~
+            tag!(":")~
+            opt!(complete!(call!(nom::space)))~
+            x: take_until_and_consume!(",")~
+            y: take_until_and_consume!(",")~
+            w: take_until_and_consume!(",")~
+            h: take_until_and_consume!("\n"),
+            || (x, y, w, h)
+        ),
+        |(x, y, w, h)| {
+            Ok::<_, ()>(CameraZone {
+                x: try!(parse_f64(x)),
+                y: try!(parse_f64(y)),
+                width: try!(parse_f64(w)),
+                height: try!(parse_f64(h)),
+            })
+        }
+    )
+}
+
+named! {
+    level_entity<EntityDef>,
+
+    map_res! (
+        chain! (
+            tag!("entity.")~
+            kind: take_until_and_consume!(":")~
+            opt!(complete!(call!(nom::space)))~
+            args: take_until_and_consume!("\n"),
+            || (kind, args)
+        ),
+        |(kind, args)| {
+            let kind = try!(str::from_utf8(kind).map_err(|_| ())).trim();
+            // Also stops a line missing its `:` from taking the next line's.
+            if kind.is_empty() || !kind.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(());
+            }
+            let args = try!(str::from_utf8(args).map_err(|_| ())).trim();
+            let args = if args.is_empty() {
+                Vec::new()
+            } else {
+                try!(args.split(',')
+                    .map(|arg| parse_f64(arg.trim().as_bytes()))
+                    .collect::<Result<Vec<_>, ()>>())
+            };
+            Ok::<_, ()>(EntityDef {
+                kind: kind.to_owned(),
+                args: args,
+            })
+        }
+    )
+}
+
+/// Any line which can come after the level's bounds.
+enum LevelEntry {
+    Item(LevelItem),
+    CameraZone(CameraZone),
+    Entity(EntityDef),
+}
+
+named! {
+    level_entry<LevelEntry>,
+    alt!(
+        call!(level_item) => { |i| LevelEntry::Item(i) }
+        | call!(level_camera_zone) => { |z| LevelEntry::CameraZone(z) }
+        | call!(level_entity) => { |e| LevelEntry::Entity(e) }
+    )
+}
+
+named! {
+    fragment_entry<LevelEntry>,
+    alt!(
+        call!(level_item) => { |i| LevelEntry::Item(i) }
+        | call!(level_entity) => { |e| LevelEntry::Entity(e) }
+    )
+}
+
 named! {
     level_end<()>,
     chain! (
@@ -175,12 +389,27 @@ named! {
         opt!(complete!(call!(nom::multispace)))~
         bounds: call!(level_bounds)~
         opt!(complete!(call!(nom::multispace)))~
-        items: terminated!(many0!(call!(level_item)), call!(level_end)),
+        physics: opt!(complete!(call!(level_physics)))~
+        opt!(complete!(call!(nom::multispace)))~
+        entries: terminated!(many0!(call!(level_entry)), call!(level_end)),
         || {
+            let mut items = Vec::new();
+            let mut camera_zones = Vec::new();
+            let mut entities = Vec::new();
+            for entry in entries {
+                match entry {
+                    LevelEntry::Item(item) => items.push(item),
+                    LevelEntry::CameraZone(zone) => camera_zones.push(zone),
+                    LevelEntry::Entity(entity) => entities.push(entity),
+                }
+            }
             Level {
                 initial_x: initial_coords.0,
                 initial_y: initial_coords.1,
                 items: items,
+                camera_zones: camera_zones,
+                entities: entities,
+                physics: physics.unwrap_or_else(Vec::new),
                 west_boundary: bounds.0,
                 south_boundary: bounds.1,
                 east_boundary: bounds.2,
@@ -205,12 +434,49 @@ pub fn load_level<T: ?Sized>(input: &T) -> Result<Level, nom::IError> where T: A
     level(input.as_ref()).to_full_result()
 }
 
-#[allow(dead_code)]
 pub fn save_level<T: ?Sized>(level: &Level, out: &mut T) -> io::Result<()> where T: Write {
     try!(write!(out, "start: {:.2},{:.2}\n\n", level.initial_x, level.initial_y));
     try!(write!(out, "bounds: {:.2},{:.2},{:.2},{:.2}\n\n",
-        level.east_boundary, level.south_boundary, level.west_boundary, level.north_boundary));
-    for item in &level.items {
+        level.west_boundary, level.south_boundary, level.east_boundary, level.north_boundary));
+    if !level.physics.is_empty() {
+        let settings = level.physics.iter()
+            .map(|update| format!("{}={}", update.name(), update.value()))
+            .collect::<Vec<_>>();
+        try!(write!(out, "physics: {}\n\n", settings.join(",")));
+    }
+    try!(save_items(&level.items, out));
+    for zone in &level.camera_zones {
+        try!(write!(out, "camera.zone: {:.2},{:.2},{:.2},{:.2}\n",
+            zone.x, zone.y, zone.width, zone.height));
+    }
+    save_entities(&level.entities, out)
+}
+
+/// Parses `platform.*` and `entity.*` lines on their own, ignoring blank lines, such as for
+/// pasting into a level.
+pub fn load_fragment<T: ?Sized>(input: &T) -> Result<Fragment, nom::IError> where T: AsRef<str> {
+    let mut fragment = Fragment::default();
+    for line in input.as_ref().lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match try!(fragment_entry(format!("{}\n", line).as_bytes()).to_full_result()) {
+            LevelEntry::Item(item) => fragment.items.push(item),
+            LevelEntry::Entity(entity) => fragment.entities.push(entity),
+            LevelEntry::CameraZone(_) => unreachable!(),
+        }
+    }
+    Ok(fragment)
+}
+
+/// Writes the fragment the same way `save_level` writes items and entities.
+pub fn save_fragment<T: ?Sized>(fragment: &Fragment, out: &mut T) -> io::Result<()>
+    where T: Write
+{
+    try!(save_items(&fragment.items, out));
+    save_entities(&fragment.entities, out)
+}
+
+/// Writes items the same way `save_level` does.
+fn save_items<T: ?Sized>(items: &[LevelItem], out: &mut T) -> io::Result<()> where T: Write {
+    for item in items {
         match *item {
             LevelItem::Box { x, y, width, height } => {
                 try!(write!(out, "platform.box: {:.2},{:.2},{:.2},{:.2}\n", x, y, width, height));
@@ -222,3 +488,189 @@ pub fn save_level<T: ?Sized>(level: &Level, out: &mut T) -> io::Result<()> where
     }
     Ok(())
 }
+
+fn save_entities<T: ?Sized>(entities: &[EntityDef], out: &mut T) -> io::Result<()>
+    where T: Write
+{
+    for entity in entities {
+        let args = entity.args.iter().map(|a| format!("{:.2}", a)).collect::<Vec<_>>();
+        try!(write!(out, "entity.{}: {}\n", entity.kind, args.join(",")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mechanics::SettingsUpdate;
+
+    use super::{CameraZone, Direction, EntityDef, Fragment, Level, LevelItem, load_fragment,
+                load_level, save_fragment, save_level};
+
+    /// A level with the given lines after its start and bounds.
+    fn level_text(entries: &str) -> String {
+        format!("start: 0,0\n\nbounds: -10,-20,30,40\n\n{}", entries)
+    }
+
+    fn round_trip(level: &Level) -> Level {
+        let mut saved = Vec::new();
+        save_level(level, &mut saved).unwrap();
+        load_level(&saved).unwrap()
+    }
+
+    #[test]
+    fn parses_entity_lines() {
+        let level = load_level(&level_text("entity.goal: 10,20\n\
+                                            entity.patroller: 5.5, -3, 40\n\
+                                            entity.hazard:\n"))
+            .unwrap();
+        let entities = level.entities.iter()
+            .map(|entity| (&entity.kind[..], entity.args.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(entities, vec![("goal", vec![10.0, 20.0]),
+                                  ("patroller", vec![5.5, -3.0, 40.0]),
+                                  ("hazard", vec![])]);
+    }
+
+    #[test]
+    fn rejects_invalid_entity_lines() {
+        assert!(load_level(&level_text("entity.goal: 10,up\n")).is_err());
+        assert!(load_level(&level_text("entity.goal: 10,,20\n")).is_err());
+        assert!(load_level(&level_text("entity.: 10,20\n")).is_err());
+        // Without its `:`, the line mustn't swallow the next one.
+        assert!(load_level(&level_text("entity.goal 10,20\nplatform.box: 0,0,1,1\n")).is_err());
+    }
+
+    #[test]
+    fn entities_round_trip() {
+        let mut level = Level::empty();
+        level.entities = vec![
+            EntityDef { kind: "goal".to_owned(), args: vec![10.0, 20.0] },
+            EntityDef { kind: "patroller".to_owned(), args: vec![-5.25, 0.0, 40.0] },
+        ];
+        let loaded = round_trip(&level);
+        assert_eq!(loaded.entities.len(), 2);
+        for (loaded, original) in loaded.entities.iter().zip(&level.entities) {
+            assert_eq!(loaded.kind, original.kind);
+            assert_eq!(loaded.args, original.args);
+        }
+    }
+
+    #[test]
+    fn camera_zones_round_trip() {
+        let mut level = Level::empty();
+        level.camera_zones = vec![
+            CameraZone { x: -100.0, y: 0.0, width: 300.0, height: 200.0 },
+            CameraZone { x: 200.5, y: -50.25, width: 100.0, height: 400.0 },
+        ];
+        let loaded = round_trip(&level);
+        let zones = loaded.camera_zones.iter()
+            .map(|zone| [zone.x, zone.y, zone.width, zone.height])
+            .collect::<Vec<_>>();
+        assert_eq!(zones, vec![[-100.0, 0.0, 300.0, 200.0], [200.5, -50.25, 100.0, 400.0]]);
+    }
+
+    #[test]
+    fn parses_camera_zone_lines() {
+        let level = load_level(&level_text("camera.zone: 1,2,3,4\n")).unwrap();
+        assert_eq!(level.camera_zones.len(), 1);
+        assert_eq!(level.camera_zones[0].width, 3.0);
+        assert!(load_level(&level_text("camera.zone: 1,2,3\n")).is_err());
+    }
+
+    #[test]
+    fn bounds_are_west_south_east_north() {
+        let level = load_level(&level_text("")).unwrap();
+        assert_eq!([level.west_boundary, level.south_boundary, level.east_boundary,
+                    level.north_boundary],
+                   [-10.0, -20.0, 30.0, 40.0]);
+
+        let mut saved = Vec::new();
+        save_level(&level, &mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        assert!(saved.contains("bounds: -10.00,-20.00,30.00,40.00\n"));
+
+        let loaded = round_trip(&level);
+        assert_eq!([loaded.west_boundary, loaded.south_boundary, loaded.east_boundary,
+                    loaded.north_boundary],
+                   [-10.0, -20.0, 30.0, 40.0]);
+    }
+
+    /// The level's `physics:` overrides, by name.
+    fn physics(level: &Level) -> Vec<(&'static str, f64)> {
+        level.physics.iter().map(|update| (update.name(), update.value())).collect()
+    }
+
+    #[test]
+    fn parses_physics_overrides() {
+        let level = load_level(&level_text("")).unwrap();
+        assert!(level.physics.is_empty());
+
+        let text = "start: 0,0\n\nbounds: -10,-20,30,40\n\n\
+                    physics: gravity_force=110, jump_boost = 300\n\n\
+                    platform.box: 0,0,10,10\n";
+        let level = load_level(text).unwrap();
+        assert_eq!(physics(&level), vec![("gravity_force", 110.0), ("jump_boost", 300.0)]);
+        assert_eq!(level.items.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_physics_overrides() {
+        let text = "start: 0,0\n\nbounds: -10,-20,30,40\n\nphysics: gravity=110\n";
+        assert!(load_level(text).is_err());
+        let text = "start: 0,0\n\nbounds: -10,-20,30,40\n\nphysics: gravity_force=lots\n";
+        assert!(load_level(text).is_err());
+    }
+
+    #[test]
+    fn physics_overrides_round_trip() {
+        let mut level = Level::empty();
+        level.physics = vec![SettingsUpdate::GravityForce(110.0), SettingsUpdate::Weight(2.5)];
+        assert_eq!(physics(&round_trip(&level)), vec![("gravity_force", 110.0), ("weight", 2.5)]);
+    }
+
+    #[test]
+    fn fragments_round_trip() {
+        let fragment = Fragment {
+            items: vec![
+                LevelItem::Box { x: -10.0, y: 20.5, width: 30.0, height: 40.0 },
+                LevelItem::Line { x: 0.0, y: 0.0, direction: Direction::North, length: 15.0 },
+                LevelItem::Line { x: 5.0, y: -5.0, direction: Direction::East, length: 25.0 },
+            ],
+            entities: vec![
+                EntityDef { kind: "hazard".to_owned(), args: vec![0.0, 0.0, 10.0, 5.0, 2.0] },
+                EntityDef { kind: "patroller".to_owned(), args: vec![100.0, 50.0] },
+            ],
+        };
+        let mut text = Vec::new();
+        save_fragment(&fragment, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(load_fragment(&text).unwrap(), fragment);
+    }
+
+    #[test]
+    fn fragments_ignore_blank_lines_and_indentation() {
+        let fragment = load_fragment("\n  platform.box: 1,2,3,4  \n\n\tentity.goal: 1,2,3,4\n")
+            .unwrap();
+        let item = LevelItem::Box { x: 1.0, y: 2.0, width: 3.0, height: 4.0 };
+        assert_eq!(fragment.items, vec![item]);
+        assert_eq!(fragment.entities.len(), 1);
+        assert_eq!(load_fragment("").unwrap(), Fragment::default());
+    }
+
+    #[test]
+    fn rejects_junk_fragments() {
+        let junk = ["hello world",
+                    "platform.box: 1,2",
+                    "platform.box: a,b,c,d",
+                    "platform.box: 1,2,3,4,5",
+                    "platform.line: 1,2,x,4",
+                    "camera.zone: 1,2,3,4",
+                    "start: 0,0",
+                    "entity.goal 1,2,3,4",
+                    "platform.box: 1,2,3,4\nnot a line",
+                    "\u{0}\u{ffff}:,,,"];
+        for text in &junk {
+            assert!(load_fragment(text).is_err(), "accepted {:?}", text);
+        }
+    }
+}
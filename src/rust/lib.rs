@@ -7,35 +7,49 @@ extern crate time;
 #[macro_use]
 extern crate nom;
 extern crate collisions;
+extern crate clipboard;
 extern crate gtk;
-
+#[cfg(feature = "sdl2")]
+extern crate sdl2_window;
+
+mod bindings;
+mod camera;
+mod config;
+mod control;
+mod entities;
 mod level_serialization;
 mod map;
 mod player;
+mod progress;
 mod scenes;
 mod mechanics;
 mod settings;
+mod sprites;
 
+use std::env::current_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 
-use graphics::ImageSize;
 use opengl_graphics::glyph_cache::GlyphCache;
 
-use opengl_graphics::Texture as OpenGlTexture;
-use opengl_graphics::TextureSettings;
 use piston::window::WindowSettings;
 use piston_window::PistonWindow;
 
-use player::{PLAYER_IMAGE_HEIGHT, PLAYER_IMAGE_WIDTH};
+use sprites::{Animation, SpriteError, SpriteSheet};
 
+/// Glutin doesn't report controller events, so gamepads only work when built with sdl2.
+#[cfg(not(feature = "sdl2"))]
 pub type Window = piston_window::PistonWindow;
+#[cfg(feature = "sdl2")]
+pub type Window = piston_window::PistonWindow<sdl2_window::Sdl2Window>;
 pub type Graphics = opengl_graphics::GlGraphics;
-pub type SettingsChannel = std::sync::mpsc::Receiver<mechanics::SettingsUpdate>;
+pub type SettingsChannel = settings::SettingsChannel;
 
 pub fn run() {
     let opengl_version = opengl_graphics::OpenGL::V3_2;
 
-    let mut window = {
+    let mut window: Window = {
         let settings = WindowSettings::new("b-w-adventures", [640u32, 480u32])
                             .exit_on_esc(false)
                             .srgb(false)
@@ -53,61 +67,84 @@ pub fn run() {
     let mut graphics = opengl_graphics::GlGraphics::new(opengl_version);
     let mut cache = GraphicsCache::load();
 
-    let (settings_send, mut settings_recv) = mpsc::channel();
+    let (settings_send, settings_recv) = mpsc::channel();
+    let (telemetry_send, telemetry_recv) = mpsc::channel();
+    let saved_settings = settings::load_saved();
+
+    settings::exec_threaded(settings_send.clone(), telemetry_recv, saved_settings.updates());
 
-    settings::exec_threaded(settings_send);
+    let control_requests = control::start_from_env(settings_send.clone(), telemetry_send.clone());
 
-    scenes::MAIN_MENU.run(&mut window, &mut graphics, &mut cache, &mut settings_recv)
+    let mut settings_channel = settings::SettingsChannel::new(settings_send, settings_recv,
+                                                              telemetry_send, saved_settings);
+    *settings_channel.bindings_mut() = bindings::Bindings::load();
+    if let Some(requests) = control_requests {
+        settings_channel.listen(requests);
+    }
+    scenes::run(&mut window, &mut graphics, &mut cache, &mut settings_channel)
 }
 
+/// Animations `player.manifest` has, one for each field of `PlayerGraphics`.
+const PLAYER_ANIMATIONS: &'static [&'static str] = &[
+    "run_left",
+    "run_right",
+    "standing_left",
+    "standing_right",
+    "pain_left",
+    "pain_right",
+    "fallen_left",
+    "fallen_right",
+];
+
 pub struct PlayerGraphics {
-    run_left: Vec<OpenGlTexture>,
-    run_right: Vec<OpenGlTexture>,
-    standing_left: OpenGlTexture,
-    standing_right: OpenGlTexture,
+    run_left: Animation,
+    run_right: Animation,
+    standing_left: Animation,
+    standing_right: Animation,
+    pain_left: Animation,
+    pain_right: Animation,
+    fallen_left: Animation,
+    fallen_right: Animation,
     /// width, height
-    dimensions: (u32, u32),
+    collision_box: (u32, u32),
 }
 
 impl PlayerGraphics {
     pub fn load() -> PlayerGraphics {
-        let run_left = load_texture_frames(&include_bytes!("../png/unarmed/runleft.png")[..], 6);
-        let run_right = load_texture_frames(&include_bytes!("../png/unarmed/runright.png")[..], 6);
-        let standing_left = load_texture(&include_bytes!("../png/unarmed/readyleft.png")[..]);
-        let standing_right = load_texture(&include_bytes!("../png/unarmed/readyright.png")[..]);
-        assert_eq!(PLAYER_IMAGE_WIDTH, run_left[0].get_width());
-        assert_eq!(PLAYER_IMAGE_WIDTH, run_right[0].get_width());
-        assert_eq!(PLAYER_IMAGE_WIDTH, standing_left.get_width());
-        assert_eq!(PLAYER_IMAGE_WIDTH, standing_right.get_width());
-        assert_eq!(PLAYER_IMAGE_HEIGHT, run_left[0].get_height());
-        assert_eq!(PLAYER_IMAGE_HEIGHT, run_right[0].get_height());
-        assert_eq!(PLAYER_IMAGE_HEIGHT, standing_left.get_height());
-        assert_eq!(PLAYER_IMAGE_HEIGHT, standing_right.get_height());
-        PlayerGraphics {
-            run_left: run_left,
-            run_right: run_right,
-            standing_left: standing_left,
-            standing_right: standing_right,
-            dimensions: (PLAYER_IMAGE_WIDTH, PLAYER_IMAGE_HEIGHT),
-        }
+        sprites::load("player", PLAYER_ANIMATIONS, PlayerGraphics::from_sheet)
     }
 
-    pub fn get_height(&self) -> u32 {
-        self.dimensions.1
+    fn from_sheet(mut sheet: SpriteSheet) -> Result<PlayerGraphics, SpriteError> {
+        Ok(PlayerGraphics {
+            run_left: try!(sheet.take("run_left")),
+            run_right: try!(sheet.take("run_right")),
+            standing_left: try!(sheet.take("standing_left")),
+            standing_right: try!(sheet.take("standing_right")),
+            pain_left: try!(sheet.take("pain_left")),
+            pain_right: try!(sheet.take("pain_right")),
+            fallen_left: try!(sheet.take("fallen_left")),
+            fallen_right: try!(sheet.take("fallen_right")),
+            collision_box: sheet.collision_box(),
+        })
     }
 
-    pub fn get_width(&self) -> u32 {
-        self.dimensions.0
-    }
-
-    pub fn get_dimensions(&self) -> (u32, u32) {
-        self.dimensions
+    /// width, height
+    pub fn collision_box(&self) -> (u32, u32) {
+        self.collision_box
     }
 }
 
+/// The collision boxes from the sprite manifests, which is all that game logic needs from them.
+#[derive(Copy, Clone, Debug)]
+pub struct Hitboxes {
+    /// width, height
+    pub player: (u32, u32),
+    pub patroller: (u32, u32),
+}
 
 pub struct GraphicsCache {
     player: PlayerGraphics,
+    patroller: entities::PatrollerGraphics,
     font: GlyphCache<'static>,
 }
 
@@ -115,38 +152,47 @@ impl GraphicsCache {
     pub fn load() -> GraphicsCache {
         GraphicsCache {
             player: PlayerGraphics::load(),
+            patroller: entities::PatrollerGraphics::load(),
             font: GlyphCache::from_bytes(include_bytes!("../ttf/Akashi.ttf")).unwrap(),
         }
     }
-}
 
-// fn load_texture<T: AsRef<Path> + ?Sized>(path: &T) -> OpenGlTexture {
-//     let file = fs::File::open(path.as_ref()).unwrap();
-//     let image = image::load(file, image::ImageFormat::PNG).unwrap().to_rgba();
-fn load_texture(bytes: &[u8]) -> OpenGlTexture {
-    let image = image::load_from_memory_with_format(bytes.as_ref(), image::ImageFormat::PNG)
-        .unwrap()
-        .to_rgba();
-
-    opengl_graphics::Texture::from_image(&image, &TextureSettings::new())
+    pub fn hitboxes(&self) -> Hitboxes {
+        Hitboxes {
+            player: self.player.collision_box(),
+            patroller: self.patroller.collision_box(),
+        }
+    }
 }
 
-// fn load_texture_frames<T: AsRef<Path> + ?Sized>(path: &T, num_frames: u32) -> Vec<OpenGlTexture> {
-//     let file = fs::File::open(path).unwrap();
-//     let mut image = image::load(file, image::ImageFormat::PNG).unwrap().to_rgba();
-fn load_texture_frames(bytes: &[u8], num_frames: u32) -> Vec<OpenGlTexture> {
-    let mut image = image::load_from_memory_with_format(bytes.as_ref(), image::ImageFormat::PNG)
-        .unwrap()
-        .to_rgba();
-    let (image_width, height) = image.dimensions();
-
-    assert_eq!(image_width % num_frames, 0);
-
-    let width = image_width / num_frames;
-
-    (0..num_frames).map(|x| {
-        let sub_image = image::SubImage::new(&mut image, x * width, 0, width, height);
-
-        opengl_graphics::Texture::from_image(&sub_image.to_image(), &TextureSettings::new())
-    }).collect()
+/// Searches the current directory and each of its parents for a directory called `name`.
+fn find_data_dir(name: &str) -> Option<PathBuf> {
+    let cwd = match current_dir() {
+        Ok(v) => v,
+        Err(_) => return None,
+    };
+    let mut current_dir: &Path = &cwd;
+    loop {
+        if let Ok(items) = fs::read_dir(&current_dir) {
+            for item in items.filter_map(Result::ok) {
+                let file_type = match item.file_type() {
+                    Ok(v) => v,
+                    Err(_) => break,
+                };
+
+                if &item.file_name()[..] == name && file_type.is_dir() {
+                    let path = item.path();
+                    // to test permissions
+                    match fs::symlink_metadata(&path) {
+                        Ok(_) => return Some(path),
+                        Err(_) => break,
+                    };
+                }
+            }
+        }
+        current_dir = match current_dir.parent() {
+            Some(v) => v,
+            None => return None,
+        };
+    }
 }
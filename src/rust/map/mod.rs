@@ -16,18 +16,6 @@ pub struct Platform {
     platform_type: PlatformType,
 }
 
-impl Platform {
-    pub fn new_box(min_x: f64, min_y: f64, len_x: f64, len_y: f64) -> Platform {
-        Platform {
-            min_x: min_x,
-            min_y: min_y,
-            len_x: len_x,
-            len_y: len_y,
-            platform_type: PlatformType::Box,
-        }
-    }
-}
-
 impl collisions::HasBounds for Platform {
     fn min_x(&self) -> f64 {
         self.min_x
@@ -66,6 +54,8 @@ pub struct Map {
     initial_y: f64,
     /// [west, south, east - west, north - south]
     boundaries: [f64; 4],
+    /// [x, y, width, height]
+    camera_zones: Vec<[f64; 4]>,
 }
 
 impl Map {
@@ -73,8 +63,13 @@ impl Map {
         &self.blocks
     }
 
-    pub fn add_block(&mut self, block: Platform) {
-        self.blocks.push(block);
+    /// Finds the index of the topmost block containing the point, treating blocks as being
+    /// `tolerance` larger on each side so thin lines can still be picked.
+    pub fn block_at(&self, x: f64, y: f64, tolerance: f64) -> Option<usize> {
+        self.blocks.iter().rposition(|block| {
+            x >= block.min_x - tolerance && x <= block.min_x + block.len_x + tolerance &&
+            y >= block.min_y - tolerance && y <= block.min_y + block.len_y + tolerance
+        })
     }
 
     pub fn boundary_collision_lines(&self) -> &[Platform] {
@@ -93,6 +88,11 @@ impl Map {
     pub fn boundaries(&self) -> [f64; 4] {
         self.boundaries
     }
+
+    /// [x, y, width, height]
+    pub fn camera_zones(&self) -> &[[f64; 4]] {
+        &self.camera_zones
+    }
 }
 
 impl<'a> From<&'a level_serialization::Level> for Map {
@@ -152,9 +152,6 @@ impl<'a> From<&'a level_serialization::Level> for Map {
             platform_type: PlatformType::Line,
         });
 
-
-        println!("{:#?}", blocks);
-
         Map {
             blocks: blocks,
             boundary_collision_lines: boundary_collision_lines,
@@ -163,9 +160,12 @@ impl<'a> From<&'a level_serialization::Level> for Map {
             boundaries: [
                 level.west_boundary,
                 level.south_boundary,
-                level.north_boundary - level.south_boundary,
                 level.east_boundary - level.west_boundary,
+                level.north_boundary - level.south_boundary,
             ],
+            camera_zones: level.camera_zones.iter()
+                .map(|zone| [zone.x, zone.y, zone.width, zone.height])
+                .collect(),
         }
     }
 }
@@ -1,11 +1,14 @@
-use std::sync::mpsc;
+use std::f64;
 
 use piston::input::*;
 
 use collisions::HasBounds;
 use collisions;
 
+use bindings::{Action, ActionEvent, ActionTracker, Bindings};
+
 use map::Map;
+use settings::Telemetry;
 
 #[derive(Copy, Clone, Debug)]
 pub enum SettingsUpdate {
@@ -20,7 +23,73 @@ pub enum SettingsUpdate {
     JumpDuration(f64),
 }
 
-pub struct PlayerSettings<'a> {
+impl SettingsUpdate {
+    /// The name of the setting changed, as used in config files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SettingsUpdate::Weight(_) => "weight",
+            SettingsUpdate::InputForce(_) => "input_force",
+            SettingsUpdate::JumpBoost(_) => "jump_boost",
+            SettingsUpdate::WallBoostX(_) => "wall_boost_x",
+            SettingsUpdate::WallBoostY(_) => "wall_boost_y",
+            SettingsUpdate::GravityForce(_) => "gravity_force",
+            SettingsUpdate::DragConstant(_) => "drag_constant",
+            SettingsUpdate::TickConstant(_) => "tick_constant",
+            SettingsUpdate::JumpDuration(_) => "jump_duration",
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        match *self {
+            SettingsUpdate::Weight(v) |
+            SettingsUpdate::InputForce(v) |
+            SettingsUpdate::JumpBoost(v) |
+            SettingsUpdate::WallBoostX(v) |
+            SettingsUpdate::WallBoostY(v) |
+            SettingsUpdate::GravityForce(v) |
+            SettingsUpdate::DragConstant(v) |
+            SettingsUpdate::TickConstant(v) |
+            SettingsUpdate::JumpDuration(v) => v,
+        }
+    }
+
+    pub fn from_name(name: &str, value: f64) -> Option<SettingsUpdate> {
+        PlayerSettings::default().updates().into_iter()
+            .find(|update| update.name() == name)
+            .map(|update| update.with_value(value))
+    }
+
+    /// (min, max, step) for adjusting this setting from the settings menus, or None if it isn't
+    /// adjustable there.
+    pub fn range(&self) -> Option<(f64, f64, f64)> {
+        match *self {
+            SettingsUpdate::Weight(_) => Some((0.0, 10.0, 0.1)),
+            SettingsUpdate::DragConstant(_) => Some((0.0, 1.0, 0.01)),
+            SettingsUpdate::TickConstant(_) => Some((0.0, 20.0, 0.1)),
+            // Not used by the physics yet.
+            SettingsUpdate::JumpDuration(_) => None,
+            _ => Some((0.0, 1000.0, 10.0)),
+        }
+    }
+
+    /// The same setting changed to a different value.
+    pub fn with_value(&self, value: f64) -> SettingsUpdate {
+        match *self {
+            SettingsUpdate::Weight(_) => SettingsUpdate::Weight(value),
+            SettingsUpdate::InputForce(_) => SettingsUpdate::InputForce(value),
+            SettingsUpdate::JumpBoost(_) => SettingsUpdate::JumpBoost(value),
+            SettingsUpdate::WallBoostX(_) => SettingsUpdate::WallBoostX(value),
+            SettingsUpdate::WallBoostY(_) => SettingsUpdate::WallBoostY(value),
+            SettingsUpdate::GravityForce(_) => SettingsUpdate::GravityForce(value),
+            SettingsUpdate::DragConstant(_) => SettingsUpdate::DragConstant(value),
+            SettingsUpdate::TickConstant(_) => SettingsUpdate::TickConstant(value),
+            SettingsUpdate::JumpDuration(_) => SettingsUpdate::JumpDuration(value),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PlayerSettings {
     pub weight: f64,
     pub input_force: f64,
     pub jump_boost: f64,
@@ -30,11 +99,10 @@ pub struct PlayerSettings<'a> {
     pub drag_constant: f64,
     pub tick_constant: f64,
     pub jump_duration: f64,
-    pub update_channel: Option<&'a mut mpsc::Receiver<SettingsUpdate>>,
 }
 
-impl<'a> Default for PlayerSettings<'a> {
-    fn default() -> PlayerSettings<'a> {
+impl Default for PlayerSettings {
+    fn default() -> PlayerSettings {
         PlayerSettings {
             weight: 3.0,
             input_force: 367.0,
@@ -45,44 +113,38 @@ impl<'a> Default for PlayerSettings<'a> {
             drag_constant: 0.08,
             tick_constant: 3.7,
             jump_duration: 20.0,
-            update_channel: None,
         }
     }
 }
 
-impl<'a> PlayerSettings<'a> {
-    fn new<'b>(sc: &'b mut ::SettingsChannel) -> PlayerSettings<'b> {
-        PlayerSettings { update_channel: Some(sc), ..PlayerSettings::default() }
-    }
-
-    fn get_updates(&mut self) {
-        if let Some(channel) = self.update_channel.as_mut() {
-            loop {
-                match channel.try_recv() {
-                    Ok(update) => {
-                        match update {
-                            SettingsUpdate::Weight(v) => self.weight = v,
-                            SettingsUpdate::InputForce(v) => self.input_force = v,
-                            SettingsUpdate::JumpBoost(v) => self.jump_boost = v,
-                            SettingsUpdate::WallBoostX(v) => self.wall_boost_x = v,
-                            SettingsUpdate::WallBoostY(v) => self.wall_boost_y = v,
-                            SettingsUpdate::GravityForce(v) => self.gravity_force = v,
-                            SettingsUpdate::DragConstant(v) => self.drag_constant = v,
-                            SettingsUpdate::TickConstant(v) => self.tick_constant = v,
-                            SettingsUpdate::JumpDuration(v) => self.jump_duration = v,
-                        }
-                    }
-                    Err(mpsc::TryRecvError::Empty) => {
-                        return;
-                    }
-                    Err(mpsc::TryRecvError::Disconnected) => {
-                        break;
-                    }
-                }
-            }
+impl PlayerSettings {
+    pub fn apply(&mut self, update: SettingsUpdate) {
+        match update {
+            SettingsUpdate::Weight(v) => self.weight = v,
+            SettingsUpdate::InputForce(v) => self.input_force = v,
+            SettingsUpdate::JumpBoost(v) => self.jump_boost = v,
+            SettingsUpdate::WallBoostX(v) => self.wall_boost_x = v,
+            SettingsUpdate::WallBoostY(v) => self.wall_boost_y = v,
+            SettingsUpdate::GravityForce(v) => self.gravity_force = v,
+            SettingsUpdate::DragConstant(v) => self.drag_constant = v,
+            SettingsUpdate::TickConstant(v) => self.tick_constant = v,
+            SettingsUpdate::JumpDuration(v) => self.jump_duration = v,
         }
-        // Will only break for disconnected
-        self.update_channel = None;
+    }
+
+    /// Every setting, as updates which would set another `PlayerSettings` to these values.
+    pub fn updates(&self) -> Vec<SettingsUpdate> {
+        vec![
+            SettingsUpdate::Weight(self.weight),
+            SettingsUpdate::InputForce(self.input_force),
+            SettingsUpdate::JumpBoost(self.jump_boost),
+            SettingsUpdate::WallBoostX(self.wall_boost_x),
+            SettingsUpdate::WallBoostY(self.wall_boost_y),
+            SettingsUpdate::GravityForce(self.gravity_force),
+            SettingsUpdate::DragConstant(self.drag_constant),
+            SettingsUpdate::TickConstant(self.tick_constant),
+            SettingsUpdate::JumpDuration(self.jump_duration),
+        ]
     }
 }
 
@@ -142,8 +204,32 @@ impl MovementState {
 //     }
 // }
 
+/// Anything which can hurt the player: hazards, enemies.
+pub trait DamageSource {
+    /// Health taken from the player on contact.
+    fn damage(&self) -> u32;
+
+    /// The point the player is knocked away from, usually the center of the source.
+    fn origin(&self) -> (f64, f64);
+
+    /// (x, y) velocity given to the player when hurt. The x velocity points away from `origin`.
+    fn knockback(&self) -> (f64, f64) {
+        (DEFAULT_KNOCKBACK_X, DEFAULT_KNOCKBACK_Y)
+    }
+}
+
+pub const PLAYER_MAX_HEALTH: u32 = 3;
+pub const DEFAULT_KNOCKBACK_X: f64 = 200.0;
+pub const DEFAULT_KNOCKBACK_Y: f64 = 150.0;
+/// Seconds the player is stunned for after being hurt.
+pub const HURT_DURATION: f64 = 0.4;
+/// Seconds after being hurt, or respawning, in which the player can't be hurt again.
+pub const INVULNERABLE_DURATION: f64 = 1.5;
+/// Seconds between the player dying and respawning.
+pub const DEATH_DURATION: f64 = 1.5;
+
 #[derive(Default)]
-pub struct PlayerState<'a> {
+pub struct PlayerState {
     pub grounded: bool,
     pub on_left_wall: bool,
     pub on_right_wall: bool,
@@ -155,23 +241,177 @@ pub struct PlayerState<'a> {
     // current_effects: Vec<Effect>,
     input_left: bool,
     input_right: bool,
-    settings: PlayerSettings<'a>,
+    collision_width: f64,
+    collision_height: f64,
+    health: u32,
+    /// seconds remaining
+    hurt_time: f64,
+    invulnerable_time: f64,
+    death_time: Option<f64>,
+    /// Number of times the player has died since being created.
+    pub deaths: u32,
+    /// Set once the player touches the level's goal, until they're reset.
+    pub reached_goal: bool,
+    spawn_x: f64,
+    spawn_y: f64,
+    bindings: Bindings,
+    actions: ActionTracker,
+    settings: PlayerSettings,
 }
 
-impl<'a> PlayerState<'a> {
-    pub fn new<'b>(x: f64, y: f64, sc: &'b mut ::SettingsChannel) -> PlayerState<'b> {
+impl PlayerState {
+    /// Starts with the channel's current settings and bindings.
+    pub fn new(x: f64, y: f64, sc: &::SettingsChannel) -> PlayerState {
+        let settings = sc.current().clone();
         PlayerState {
             absolute_x: x,
             absolute_y: y,
-            settings: PlayerSettings::new(sc),
+            collision_width: PLAYER_COLLISION_WIDTH as f64,
+            collision_height: PLAYER_COLLISION_HEIGHT as f64,
+            health: PLAYER_MAX_HEALTH,
+            spawn_x: x,
+            spawn_y: y,
+            bindings: sc.bindings().clone(),
+            settings: settings,
             ..PlayerState::default()
         }
     }
 
-    fn tick(&mut self, args: &UpdateArgs, map: &Map) {
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.death_time.is_some()
+    }
+
+    /// True while stunned from a recent hit.
+    pub fn is_hurt(&self) -> bool {
+        self.hurt_time > 0.0
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_time > 0.0 || self.is_dead()
+    }
+
+    /// Hurts the player and knocks them away from the source, unless they're invulnerable.
+    /// Returns whether any damage was done.
+    pub fn hurt(&mut self, source: &DamageSource) -> bool {
+        if self.is_invulnerable() {
+            return false;
+        }
+        let (knockback_x, knockback_y) = source.knockback();
+        let (origin_x, _) = source.origin();
+        if self.absolute_x + self.collision_width / 2.0 < origin_x {
+            self.velocity_x = -knockback_x;
+            self.last_movement = MovementState::StillRight;
+        } else {
+            self.velocity_x = knockback_x;
+            self.last_movement = MovementState::StillLeft;
+        }
+        self.velocity_y = knockback_y;
+        self.grounded = false;
+
+        self.health = self.health.saturating_sub(source.damage());
+        if self.health == 0 {
+            self.death_time = Some(DEATH_DURATION);
+            self.deaths += 1;
+        } else {
+            self.hurt_time = HURT_DURATION;
+            self.invulnerable_time = INVULNERABLE_DURATION;
+        }
+        true
+    }
+
+    /// Launches the player upwards, for example after landing on an enemy.
+    pub fn bounce(&mut self, velocity_y: f64) {
+        self.velocity_y = velocity_y;
+        self.grounded = false;
+    }
+
+    fn respawn(&mut self) {
+        self.absolute_x = self.spawn_x;
+        self.absolute_y = self.spawn_y;
+        self.velocity_x = 0.0;
+        self.velocity_y = 0.0;
+        self.health = PLAYER_MAX_HEALTH;
+        self.death_time = None;
+        self.hurt_time = 0.0;
+        self.invulnerable_time = INVULNERABLE_DURATION;
+        self.last_movement = MovementState::default();
+    }
+
+    /// Puts the player at (x, y) standing still with full health, and makes it the respawn point.
+    pub fn reset_at(&mut self, x: f64, y: f64) {
+        self.spawn_x = x;
+        self.spawn_y = y;
+        self.respawn();
+        self.invulnerable_time = 0.0;
+        self.reached_goal = false;
+        self.clear_input();
+    }
+
+    /// Lets go of every input, for when releases may have been missed.
+    pub fn clear_input(&mut self) {
+        self.input_left = false;
+        self.input_right = false;
+        self.actions = ActionTracker::default();
+    }
+
+    /// Changes settings for this player only, such as a level's physics overrides. Later changes
+    /// from the settings channel still apply on top.
+    pub fn override_settings(&mut self, updates: &[SettingsUpdate], sc: &::SettingsChannel) {
+        for &update in updates {
+            self.settings.apply(update);
+        }
+        sc.report(Telemetry::Overrides(updates.to_vec()));
+    }
+
+    /// Starts again from the channel's current settings and bindings, for after they've been
+    /// changed somewhere other than through the channel, such as the options menu.
+    pub fn reload_settings(&mut self, overrides: &[SettingsUpdate], sc: &::SettingsChannel) {
+        self.settings = sc.current().clone();
+        self.bindings = sc.bindings().clone();
+        self.override_settings(overrides, sc);
+    }
+
+    /// Sends the player's current state to the settings window.
+    pub fn report(&self, fps: f64, sc: &::SettingsChannel) {
+        sc.report(Telemetry::Player {
+            x: self.absolute_x,
+            y: self.absolute_y,
+            velocity_x: self.velocity_x,
+            velocity_y: self.velocity_y,
+            grounded: self.grounded,
+            on_left_wall: self.on_left_wall,
+            on_right_wall: self.on_right_wall,
+            fps: fps,
+        });
+    }
+
+    /// Sets the (width, height) of the box used for collisions with the map.
+    pub fn set_collision_box(&mut self, (width, height): (u32, u32)) {
+        self.collision_width = width as f64;
+        self.collision_height = height as f64;
+    }
+
+    /// Moves the player, after applying any changes sent to the settings channel.
+    pub fn update(&mut self, args: &UpdateArgs, map: &Map, sc: &mut ::SettingsChannel) {
         let delta_time = args.dt;
 
-        self.settings.get_updates();
+        for update in sc.poll() {
+            self.settings.apply(update);
+        }
+
+        self.hurt_time = f64::max(0.0, self.hurt_time - delta_time);
+        self.invulnerable_time = f64::max(0.0, self.invulnerable_time - delta_time);
+        if let Some(remaining) = self.death_time {
+            if remaining <= delta_time {
+                self.respawn();
+                return;
+            }
+            self.death_time = Some(remaining - delta_time);
+        }
 
         // {
         //     let mut effects = Vec::new();
@@ -186,7 +426,9 @@ impl<'a> PlayerState<'a> {
         let mut force_x = 0.0;
         let mut force_y = 0.0;
 
-        match (self.input_left, self.input_right) {
+        let controllable = !self.is_dead() && !self.is_hurt();
+
+        match (self.input_left && controllable, self.input_right && controllable) {
             (false, true) => {
                 force_x += self.settings.input_force;
                 self.last_movement = MovementState::MovingRight;
@@ -250,7 +492,18 @@ impl<'a> PlayerState<'a> {
         }
     }
 
+    pub fn velocity_x(&self) -> f64 {
+        self.velocity_x
+    }
+
+    pub fn velocity_y(&self) -> f64 {
+        self.velocity_y
+    }
+
     fn jump(&mut self) {
+        if self.is_dead() || self.is_hurt() {
+            return;
+        }
         if self.grounded {
             self.velocity_y += self.settings.jump_boost;
         } else if self.on_left_wall {
@@ -264,30 +517,25 @@ impl<'a> PlayerState<'a> {
         }
     }
 
-    pub fn update(&mut self, event: &Input, map: &Map) {
-        event.press(|button| {
-            match button {
-                Button::Keyboard(Key::Up) => self.jump(),
-                Button::Keyboard(Key::Left) => self.input_left = true,
-                Button::Keyboard(Key::Right) => self.input_right = true,
+    /// Handles presses and releases of the player's controls.
+    pub fn input(&mut self, event: &Input) {
+        for action_event in self.actions.events(&self.bindings, event) {
+            match action_event {
+                ActionEvent::Pressed(Action::Jump) => self.jump(),
+                ActionEvent::Pressed(Action::MoveLeft) => self.input_left = true,
+                ActionEvent::Pressed(Action::MoveRight) => self.input_right = true,
+                ActionEvent::Released(Action::MoveLeft) => self.input_left = false,
+                ActionEvent::Released(Action::MoveRight) => self.input_right = false,
                 _ => (),
             }
-        });
-        event.release(|button| {
-            match button {
-                Button::Keyboard(Key::Left) => self.input_left = false,
-                Button::Keyboard(Key::Right) => self.input_right = false,
-                _ => (),
-            }
-        });
-        event.update(|args| self.tick(args, map));
+        }
     }
 }
 
 pub const PLAYER_COLLISION_WIDTH: u32 = 10;
 pub const PLAYER_COLLISION_HEIGHT: u32 = 20;
 
-impl<'a> collisions::HasBounds for PlayerState<'a> {
+impl collisions::HasBounds for PlayerState {
     fn min_x(&self) -> f64 {
         self.absolute_x
     }
@@ -297,10 +545,120 @@ impl<'a> collisions::HasBounds for PlayerState<'a> {
     }
 
     fn len_x(&self) -> f64 {
-        PLAYER_COLLISION_WIDTH as f64
+        self.collision_width
     }
 
     fn len_y(&self) -> f64 {
-        PLAYER_COLLISION_HEIGHT as f64
+        self.collision_height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use piston::input::UpdateArgs;
+
+    use ::SettingsChannel;
+    use level_serialization::Level;
+    use map::Map;
+
+    use super::{DEATH_DURATION, DEFAULT_KNOCKBACK_X, DamageSource, INVULNERABLE_DURATION,
+                PLAYER_MAX_HEALTH, PlayerSettings, PlayerState};
+
+    struct Spike {
+        x: f64,
+        damage: u32,
+    }
+
+    impl DamageSource for Spike {
+        fn damage(&self) -> u32 {
+            self.damage
+        }
+
+        fn origin(&self) -> (f64, f64) {
+            (self.x, 0.0)
+        }
+    }
+
+    fn settings_channel() -> SettingsChannel {
+        let (send, recv) = mpsc::channel();
+        let (telemetry, _) = mpsc::channel();
+        SettingsChannel::new(send, recv, telemetry, PlayerSettings::default())
+    }
+
+    const STEP: f64 = 0.01;
+
+    /// Advances the player by one step, on a map with nothing but its bounds.
+    fn step(player: &mut PlayerState, sc: &mut SettingsChannel) {
+        player.update(&UpdateArgs { dt: STEP }, &Map::from(&Level::empty()), sc);
+    }
+
+    fn run(player: &mut PlayerState, sc: &mut SettingsChannel, seconds: f64) {
+        for _ in 0..(seconds / STEP).round() as usize {
+            step(player, sc);
+        }
+    }
+
+    #[test]
+    fn hit_knocks_back_away_from_source() {
+        let sc = settings_channel();
+        let mut player = PlayerState::new(0.0, 0.0, &sc);
+        assert!(player.hurt(&Spike { x: 50.0, damage: 1 }));
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH - 1);
+        assert_eq!(player.velocity_x, -DEFAULT_KNOCKBACK_X);
+        assert!(player.velocity_y > 0.0);
+        assert!(player.is_hurt());
+    }
+
+    #[test]
+    fn hits_while_invulnerable_are_ignored() {
+        let sc = settings_channel();
+        let mut player = PlayerState::new(0.0, 0.0, &sc);
+        assert!(player.hurt(&Spike { x: 50.0, damage: 1 }));
+        assert!(player.is_invulnerable());
+        assert!(!player.hurt(&Spike { x: -50.0, damage: 1 }));
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH - 1);
+        assert_eq!(player.velocity_x, -DEFAULT_KNOCKBACK_X);
+    }
+
+    #[test]
+    fn invulnerability_expires() {
+        let mut sc = settings_channel();
+        let mut player = PlayerState::new(0.0, 0.0, &sc);
+        assert!(player.hurt(&Spike { x: 50.0, damage: 1 }));
+        run(&mut player, &mut sc, INVULNERABLE_DURATION - 0.1);
+        assert!(player.is_invulnerable());
+        run(&mut player, &mut sc, 0.2);
+        assert!(!player.is_invulnerable());
+        assert!(player.hurt(&Spike { x: 50.0, damage: 1 }));
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH - 2);
+    }
+
+    #[test]
+    fn dying_respawns_at_start_with_full_health() {
+        let mut sc = settings_channel();
+        let mut player = PlayerState::new(0.0, 0.0, &sc);
+        player.reset_at(50.0, 80.0);
+        player.absolute_x = 300.0;
+        assert!(player.hurt(&Spike { x: 400.0, damage: PLAYER_MAX_HEALTH }));
+        assert!(player.is_dead());
+        assert_eq!(player.health(), 0);
+        assert_eq!(player.deaths, 1);
+        assert!(!player.hurt(&Spike { x: 400.0, damage: 1 }));
+
+        run(&mut player, &mut sc, 0.5);
+        assert!(player.is_dead());
+        let mut steps = 0;
+        while player.is_dead() {
+            step(&mut player, &mut sc);
+            steps += 1;
+            assert!(steps as f64 * STEP <= DEATH_DURATION, "never respawned");
+        }
+        assert_eq!((player.absolute_x, player.absolute_y), (50.0, 80.0));
+        assert_eq!(player.health(), PLAYER_MAX_HEALTH);
+        assert_eq!(player.deaths, 1);
+        // Respawning gives a moment before the player can be hurt again.
+        assert!(player.is_invulnerable());
     }
 }
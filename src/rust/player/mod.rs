@@ -1,94 +1,77 @@
-use std::f64;
-
 use piston::input::*;
-use opengl_graphics::Texture as OpenGlTexture;
+
+use collisions::HasBounds;
 
 use super::PlayerGraphics;
 use super::SettingsChannel;
+use camera::CameraTarget;
 use map::Map;
 use mechanics::PlayerState;
 use mechanics::MovementState;
+use sprites::Frame;
 
-pub const PLAYER_IMAGE_WIDTH: u32 = 32;
-pub const PLAYER_IMAGE_HEIGHT: u32 = 20;
-pub const PLAYER_IMAGE_Y_OFFSET: f64 = 0.0;
-pub const PLAYER_IMAGE_X_OFFSET: f64 = -11.0;
+/// Blinks per second while invulnerable.
+pub const INVULNERABLE_BLINK_RATE: f64 = 10.0;
 
 #[derive(Default)]
-pub struct Player<'a> {
-    pub last_scroll_x: f64,
-    pub last_scroll_y: f64,
-    pub state: PlayerState<'a>,
+pub struct Player {
+    /// Seconds since the player was created, used to pick animation frames.
+    pub animation_time: f64,
+    pub state: PlayerState,
 }
 
-impl<'a> Player<'a> {
-    pub fn new<'b>(x: f64, y: f64, sc: &'b mut SettingsChannel) -> Player<'b> {
+impl Player {
+    pub fn new(x: f64, y: f64, sc: &SettingsChannel) -> Player {
         Player {
             state: PlayerState::new(x, y, sc),
-            last_scroll_x: x,
-            last_scroll_y: y,
+            animation_time: 0.0,
         }
     }
 
-    pub fn event(&mut self, event: &Input, map: &Map) {
-        self.state.update(event, map);
+    pub fn input(&mut self, event: &Input) {
+        self.state.input(event);
     }
 
-    pub fn get_current_image<'b>(&self, cache: &'b PlayerGraphics) -> &'b OpenGlTexture {
-        if self.state.grounded {
+    pub fn update(&mut self, args: &UpdateArgs, map: &Map, sc: &mut SettingsChannel) {
+        self.state.update(args, map, sc);
+        self.animation_time += args.dt;
+    }
+
+    pub fn get_current_frame<'b>(&self, cache: &'b PlayerGraphics) -> Frame<'b> {
+        let facing_left = match self.state.last_movement {
+            MovementState::StillLeft | MovementState::MovingLeft => true,
+            MovementState::StillRight | MovementState::MovingRight => false,
+        };
+        if self.state.is_dead() {
+            if facing_left { cache.fallen_left.first() } else { cache.fallen_right.first() }
+        } else if self.state.is_hurt() {
+            if facing_left { cache.pain_left.first() } else { cache.pain_right.first() }
+        } else if self.state.grounded {
             match self.state.last_movement {
-                MovementState::StillLeft => {
-                    &cache.standing_left
-                }
-                MovementState::StillRight => {
-                    &cache.standing_right
-                }
-                MovementState::MovingLeft => {
-                    &cache.run_left[5 - self.state.absolute_x.ceil() as usize / 4 % 6]
-                }
-                MovementState::MovingRight => {
-                    &cache.run_right[self.state.absolute_x.ceil() as usize / 4 % 6]
-                }
+                MovementState::StillLeft => cache.standing_left.frame(self.animation_time),
+                MovementState::StillRight => cache.standing_right.frame(self.animation_time),
+                MovementState::MovingLeft => cache.run_left.frame(self.animation_time),
+                MovementState::MovingRight => cache.run_right.frame(self.animation_time),
             }
+        } else if facing_left {
+            cache.run_left.first()
         } else {
-            match self.state.last_movement {
-                MovementState::StillLeft | MovementState::MovingLeft => {
-                    &cache.run_left[0]
-                }
-                MovementState::StillRight | MovementState::MovingRight => {
-                    &cache.run_right[0]
-                }
-            }
+            cache.run_right.first()
         }
     }
 
-    /// Takes screen width and height, gives (scroll_x, scroll_y)
-    pub fn calculate_scroll(&mut self, width: f64, height: f64) -> (f64, f64) {
-        let allowance_x = f64::max(width / 5.0, 100.0);
-        let allowance_y = f64::max(height / 5.0, 100.0);
-
-        let half_width = width / 2.0;
-        let half_height = height / 2.0;
-
-        let scroll_x = if self.state.absolute_x > self.last_scroll_x + half_width - allowance_x {
-            self.state.absolute_x - half_width + allowance_x
-        } else if self.state.absolute_x < self.last_scroll_x + allowance_x - width / 2.0 {
-            self.state.absolute_x - allowance_x + width / 2.0
-        } else {
-            self.last_scroll_x
-        };
-
-        let scroll_y = if self.state.absolute_y > self.last_scroll_y + half_height - allowance_y {
-            self.state.absolute_y - half_height + allowance_y
-        } else if self.state.absolute_y < self.last_scroll_y + allowance_y - half_height {
-            self.state.absolute_y - allowance_y + half_height
-        } else {
-            self.last_scroll_y
-        };
-
-        self.last_scroll_x = scroll_x;
-        self.last_scroll_y = scroll_y;
+    /// False for every other blink while invulnerable after being hurt.
+    pub fn is_visible(&self) -> bool {
+        self.state.is_dead() || !self.state.is_invulnerable() ||
+        (self.animation_time * INVULNERABLE_BLINK_RATE) as u64 % 2 == 0
+    }
 
-        (scroll_x.floor(), scroll_y.floor())
+    pub fn camera_target(&self) -> CameraTarget {
+        CameraTarget {
+            x: self.state.min_x() + self.state.len_x() / 2.0,
+            y: self.state.min_y() + self.state.len_y() / 2.0,
+            velocity_x: self.state.velocity_x(),
+            grounded: self.state.grounded,
+        }
     }
 }
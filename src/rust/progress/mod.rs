@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+use config;
+
+/// File in the config dir which level progress is saved to.
+const PROGRESS_FILE: &'static str = "progress.conf";
+
+/// What the player has done in one level.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LevelProgress {
+    pub completed: bool,
+    /// Fewest seconds the level has been completed in.
+    pub best_time: Option<f64>,
+    /// Deaths over every attempt at the level.
+    pub deaths: u32,
+}
+
+/// Progress through every level played, by level name. Saved as lines like `x1.deaths: 3`.
+#[derive(Default)]
+pub struct Progress {
+    levels: BTreeMap<String, LevelProgress>,
+}
+
+impl Progress {
+    /// Loads the saved progress, or starts with none if there isn't any.
+    pub fn load() -> Progress {
+        let mut progress = Progress::default();
+        let text = match config::read(PROGRESS_FILE) {
+            Ok(Some(text)) => text,
+            Ok(None) => return progress,
+            Err(e) => {
+                println!("Failed to read {}, starting with no progress: {}", PROGRESS_FILE, e);
+                return progress;
+            }
+        };
+        let pairs = match config::parse(&text) {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                println!("Failed to load {}, starting with no progress: {}", PROGRESS_FILE, e);
+                return progress;
+            }
+        };
+        for (key, value) in pairs {
+            if let Err(e) = progress.load_value(key, value) {
+                println!("Ignoring line in {}: {}", PROGRESS_FILE, e);
+            }
+        }
+        progress
+    }
+
+    fn load_value(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value for {}: {}", key, value);
+        // Level names can't contain dots, so the last one separates the name from the field.
+        let (name, field) = match key.rfind('.') {
+            Some(pos) => (&key[..pos], &key[pos + 1..]),
+            None => return Err(format!("expected `level.field`, found {}", key)),
+        };
+        if !["completed", "best_time", "deaths"].contains(&field) {
+            return Err(format!("unknown field {}", key));
+        }
+        let level = self.level_mut(name);
+        match field {
+            "completed" => level.completed = try!(value.parse().map_err(|_| invalid())),
+            "best_time" => level.best_time = Some(try!(value.parse().map_err(|_| invalid()))),
+            _ => level.deaths = try!(value.parse().map_err(|_| invalid())),
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let mut text = String::new();
+        for (name, level) in &self.levels {
+            text.push_str(&format!("{}.completed: {}\n", name, level.completed));
+            if let Some(time) = level.best_time {
+                text.push_str(&format!("{}.best_time: {:.2}\n", name, time));
+            }
+            text.push_str(&format!("{}.deaths: {}\n", name, level.deaths));
+        }
+        config::write(PROGRESS_FILE, &text)
+    }
+
+    pub fn level(&self, name: &str) -> LevelProgress {
+        self.levels.get(name).cloned().unwrap_or_else(LevelProgress::default)
+    }
+
+    pub fn add_deaths(&mut self, name: &str, deaths: u32) {
+        self.level_mut(name).deaths += deaths;
+    }
+
+    /// Marks the level completed, keeping `time` if it's the best so far. Gives whether it was.
+    pub fn complete(&mut self, name: &str, time: f64) -> bool {
+        let level = self.level_mut(name);
+        level.completed = true;
+        let best = level.best_time.map_or(true, |best| time < best);
+        if best {
+            level.best_time = Some(time);
+        }
+        best
+    }
+
+    fn level_mut(&mut self, name: &str) -> &mut LevelProgress {
+        self.levels.entry(name.to_owned()).or_insert_with(LevelProgress::default)
+    }
+}
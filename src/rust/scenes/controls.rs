@@ -0,0 +1,124 @@
+use piston::input::{Button, Input, Key, PressEvent, RenderArgs};
+use graphics;
+
+use bindings::{self, ACTIONS, Action, Bindings};
+use super::{Menu, MenuInput, MenuStep, draw_text, render_menu};
+use super::stack::{Scene, SceneContext, SceneResult, Transition};
+
+/// Lists every action with its keys. Enter or right adds a key to an action, and left removes
+/// the last one added.
+pub struct ControlsScene {
+    menu: Menu,
+    /// The action a key is being waited for.
+    binding: Option<Action>,
+}
+
+impl ControlsScene {
+    pub fn new() -> ControlsScene {
+        ControlsScene {
+            menu: Menu::default(),
+            binding: None,
+        }
+    }
+}
+
+impl Scene for ControlsScene {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let (index, adding) = match self.menu.step(ctx.sc.bindings(), event, ACTIONS.len() + 2) {
+            MenuStep::Stay => return Transition::Stay,
+            MenuStep::Back => return Transition::Pop,
+            MenuStep::Done(MenuInput::Chosen(index)) => (index, true),
+            MenuStep::Done(MenuInput::Adjusted(index, direction)) if index < ACTIONS.len() => {
+                (index, direction > 0)
+            }
+            MenuStep::Done(MenuInput::Adjusted(..)) => return Transition::Stay,
+        };
+
+        match ACTIONS.get(index) {
+            Some(&action) if adding => {
+                self.binding = Some(action);
+                let title = format!("PRESS A KEY FOR {}", action_name(action));
+                return Transition::Push(Box::new(WaitForKey { title: title }));
+            }
+            Some(&action) => ctx.sc.bindings_mut().unbind_last(action),
+            None if index == ACTIONS.len() => *ctx.sc.bindings_mut() = Bindings::default(),
+            None => {
+                match ctx.sc.bindings().save() {
+                    Ok(path) => println!("Saved key bindings to {}", path.display()),
+                    Err(e) => println!("Failed to save key bindings: {}", e),
+                }
+            }
+        }
+        Transition::Stay
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let mut labels = ACTIONS.iter().map(|&action| {
+            let keys = ctx.sc.bindings().keys(action).into_iter()
+                .map(|key| bindings::key_name(key).to_uppercase())
+                .collect::<Vec<_>>();
+            format!("{}: {}", action_name(action), keys.join(", "))
+        }).collect::<Vec<_>>();
+        labels.push("RESET TO DEFAULTS".to_owned());
+        labels.push("SAVE".to_owned());
+
+        render_menu(ctx, args, "CONTROLS", &labels, self.menu.selected, false);
+    }
+
+    fn resume(&mut self, ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        if let (Some(action), Some(SceneResult::Key(key))) = (self.binding.take(), result) {
+            ctx.sc.bindings_mut().bind(action, key);
+        }
+        Transition::Stay
+    }
+}
+
+fn action_name(action: Action) -> String {
+    action.name().replace('_', " ").to_uppercase()
+}
+
+/// Shows `title` until a key is pressed, giving the key. Going back gives nothing instead.
+struct WaitForKey {
+    title: String,
+}
+
+impl Scene for WaitForKey {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        match event.press_args() {
+            Some(Button::Keyboard(Key::Escape)) => Transition::Pop,
+            Some(button) if ctx.sc.bindings().actions(&button).contains(&Action::Back) => {
+                Transition::Pop
+            }
+            Some(Button::Keyboard(key)) => Transition::PopWith(SceneResult::Key(key)),
+            _ => Transition::Stay,
+        }
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let screen_width = args.width as f64;
+        let screen_height = args.height as f64;
+
+        let viewport = graphics::Viewport {
+            rect: [0, 0, args.width as i32, args.height as i32],
+            draw_size: [1; 2],
+            window_size: [1; 2],
+        };
+
+        let title = &self.title;
+        let cache = &mut *ctx.cache;
+        ctx.graphics.draw(viewport, |context, graphics| {
+            graphics::clear(graphics::color::BLACK, graphics);
+            let width = f64::min(screen_width * 0.8, 400.0).floor();
+            let height = 20.0;
+            let x_pos = ((screen_width - width) / 2.0).floor();
+
+            draw_text(
+                [x_pos, screen_height * 0.2, width, height * 1.5],
+                title,
+                (height * 1.5) as u32,
+                graphics::color::WHITE,
+                cache, &context, graphics,
+            );
+        })
+    }
+}
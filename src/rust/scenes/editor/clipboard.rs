@@ -0,0 +1,42 @@
+use clipboard::{ClipboardContext, ClipboardProvider};
+
+/// The system clipboard, falling back to one only shared within the game if it isn't available.
+pub struct Clipboard {
+    system: Option<ClipboardContext>,
+    fallback: String,
+}
+
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        let system = match ClipboardProvider::new() {
+            Ok(context) => Some(context),
+            Err(e) => {
+                println!("System clipboard unavailable, copying within the editor only: {}", e);
+                None
+            }
+        };
+        Clipboard {
+            system: system,
+            fallback: String::new(),
+        }
+    }
+
+    pub fn set(&mut self, text: String) {
+        if let Some(ref mut context) = self.system {
+            if let Err(e) = context.set_contents(text.clone()) {
+                println!("Failed to set system clipboard: {}", e);
+            }
+        }
+        self.fallback = text;
+    }
+
+    pub fn get(&mut self) -> String {
+        if let Some(ref mut context) = self.system {
+            match context.get_contents() {
+                Ok(text) => return text,
+                Err(e) => println!("Failed to read system clipboard: {}", e),
+            }
+        }
+        self.fallback.clone()
+    }
+}
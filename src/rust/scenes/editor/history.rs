@@ -0,0 +1,259 @@
+use level_serialization::{EntityDef, Level, LevelItem};
+
+/// A single reversible change to a level.
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+    AddItem { index: usize, item: LevelItem },
+    AddEntity { index: usize, entity: EntityDef },
+    DeleteItem { index: usize, item: LevelItem },
+    MoveItem { index: usize, from: LevelItem, to: LevelItem },
+    ResizeItem { index: usize, from: LevelItem, to: LevelItem },
+    /// Any other change to a single item, such as typing values into the property panel.
+    EditItem { index: usize, from: LevelItem, to: LevelItem },
+    SetStart { from: (f64, f64), to: (f64, f64) },
+    /// [west, south, east, north]
+    SetBounds { from: [f64; 4], to: [f64; 4] },
+    /// Several commands undone and redone together, applied in order.
+    Batch(Vec<EditCommand>),
+}
+
+impl EditCommand {
+    pub fn apply(&self, level: &mut Level) {
+        match *self {
+            EditCommand::AddItem { index, item } => level.items.insert(index, item),
+            EditCommand::AddEntity { index, ref entity } => {
+                level.entities.insert(index, entity.clone())
+            }
+            EditCommand::DeleteItem { index, .. } => {
+                level.items.remove(index);
+            }
+            EditCommand::MoveItem { index, to, .. } |
+            EditCommand::ResizeItem { index, to, .. } |
+            EditCommand::EditItem { index, to, .. } => level.items[index] = to,
+            EditCommand::SetStart { to, .. } => set_start(level, to),
+            EditCommand::SetBounds { to, .. } => set_bounds(level, to),
+            EditCommand::Batch(ref commands) => {
+                for command in commands {
+                    command.apply(level);
+                }
+            }
+        }
+    }
+
+    pub fn revert(&self, level: &mut Level) {
+        match *self {
+            EditCommand::AddItem { index, .. } => {
+                level.items.remove(index);
+            }
+            EditCommand::AddEntity { index, .. } => {
+                level.entities.remove(index);
+            }
+            EditCommand::DeleteItem { index, item } => level.items.insert(index, item),
+            EditCommand::MoveItem { index, from, .. } |
+            EditCommand::ResizeItem { index, from, .. } |
+            EditCommand::EditItem { index, from, .. } => level.items[index] = from,
+            EditCommand::SetStart { from, .. } => set_start(level, from),
+            EditCommand::SetBounds { from, .. } => set_bounds(level, from),
+            EditCommand::Batch(ref commands) => {
+                for command in commands.iter().rev() {
+                    command.revert(level);
+                }
+            }
+        }
+    }
+}
+
+/// [west, south, east, north]
+pub fn bounds(level: &Level) -> [f64; 4] {
+    [level.west_boundary, level.south_boundary, level.east_boundary, level.north_boundary]
+}
+
+/// Same order as `bounds`.
+pub fn set_bounds(level: &mut Level, bounds: [f64; 4]) {
+    level.west_boundary = bounds[0];
+    level.south_boundary = bounds[1];
+    level.east_boundary = bounds[2];
+    level.north_boundary = bounds[3];
+}
+
+fn set_start(level: &mut Level, (x, y): (f64, f64)) {
+    level.initial_x = x;
+    level.initial_y = y;
+}
+
+/// Undo and redo stacks for one editing session.
+pub struct History {
+    undo: Vec<EditCommand>,
+    redo: Vec<EditCommand>,
+    /// Length of `undo` when the level was last saved, or None if that state can't be returned
+    /// to anymore.
+    saved_at: Option<usize>,
+}
+
+impl Default for History {
+    /// The level starts out the same as its file.
+    fn default() -> History {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved_at: Some(0),
+        }
+    }
+}
+
+impl History {
+    /// Applies a command to the level and records it.
+    pub fn execute(&mut self, level: &mut Level, command: EditCommand) {
+        command.apply(level);
+        self.record(command);
+    }
+
+    /// Records a command which has already been applied, such as a drag shown live.
+    pub fn record(&mut self, command: EditCommand) {
+        // The saved state was undone, and is about to be dropped from the redo stack.
+        if self.saved_at.map_or(false, |saved| saved > self.undo.len()) {
+            self.saved_at = None;
+        }
+        self.undo.push(command);
+        self.redo.clear();
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo.len());
+    }
+
+    /// Whether the level is the same as when it was last saved.
+    pub fn is_saved(&self) -> bool {
+        self.saved_at == Some(self.undo.len())
+    }
+
+    /// Reverts the last command, returning false if there was nothing to undo.
+    pub fn undo(&mut self, level: &mut Level) -> bool {
+        match self.undo.pop() {
+            Some(command) => {
+                command.revert(level);
+                self.redo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the last undone command, returning false if there was nothing to redo.
+    pub fn redo(&mut self, level: &mut Level) -> bool {
+        match self.redo.pop() {
+            Some(command) => {
+                command.apply(level);
+                self.undo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use level_serialization::{EntityDef, Level, LevelItem};
+    use super::{EditCommand, History};
+
+    fn block(x: f64) -> LevelItem {
+        LevelItem::Box { x: x, y: 0.0, width: 10.0, height: 10.0 }
+    }
+
+    fn level_with(items: &[LevelItem]) -> Level {
+        let mut level = Level::empty();
+        level.items = items.to_vec();
+        level
+    }
+
+    #[test]
+    fn execute_undo_redo_round_trip() {
+        let mut level = level_with(&[block(0.0)]);
+        let mut history = History::default();
+        history.execute(&mut level, EditCommand::MoveItem {
+            index: 0,
+            from: block(0.0),
+            to: block(50.0),
+        });
+        assert_eq!(level.items, vec![block(50.0)]);
+
+        assert!(history.undo(&mut level));
+        assert_eq!(level.items, vec![block(0.0)]);
+
+        assert!(history.redo(&mut level));
+        assert_eq!(level.items, vec![block(50.0)]);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut level = level_with(&[]);
+        let mut history = History::default();
+        history.execute(&mut level, EditCommand::AddItem { index: 0, item: block(0.0) });
+        assert!(history.undo(&mut level));
+
+        history.execute(&mut level, EditCommand::AddItem { index: 0, item: block(20.0) });
+        assert!(!history.redo(&mut level));
+        assert_eq!(level.items, vec![block(20.0)]);
+    }
+
+    #[test]
+    fn batch_undo_reverts_in_reverse_order() {
+        let mut level = level_with(&[block(0.0), block(10.0), block(20.0)]);
+        let mut history = History::default();
+        // Deleting from the end first keeps the earlier indices valid, so undoing has to put
+        // the items back in the opposite order.
+        history.execute(&mut level, EditCommand::Batch(vec![
+            EditCommand::DeleteItem { index: 2, item: block(20.0) },
+            EditCommand::DeleteItem { index: 0, item: block(0.0) },
+        ]));
+        assert_eq!(level.items, vec![block(10.0)]);
+
+        assert!(history.undo(&mut level));
+        assert_eq!(level.items, vec![block(0.0), block(10.0), block(20.0)]);
+    }
+
+    #[test]
+    fn tracks_saved_state() {
+        let mut level = level_with(&[]);
+        let mut history = History::default();
+        assert!(history.is_saved());
+        history.execute(&mut level, EditCommand::AddItem { index: 0, item: block(0.0) });
+        assert!(!history.is_saved());
+        history.mark_saved();
+        history.execute(&mut level, EditCommand::AddItem { index: 1, item: block(10.0) });
+        assert!(history.undo(&mut level));
+        assert!(history.is_saved());
+
+        // Undoing past the save and making another edit means it can't be returned to.
+        assert!(history.undo(&mut level));
+        history.execute(&mut level, EditCommand::AddItem { index: 0, item: block(20.0) });
+        assert!(!history.is_saved());
+        assert!(history.undo(&mut level));
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn undo_on_empty_history_does_nothing() {
+        let mut level = level_with(&[block(0.0)]);
+        let mut history = History::default();
+        assert!(!history.undo(&mut level));
+        assert!(!history.redo(&mut level));
+        assert_eq!(level.items, vec![block(0.0)]);
+    }
+
+    #[test]
+    fn undoes_added_entities() {
+        let mut level = level_with(&[]);
+        let mut history = History::default();
+        let goal = EntityDef { kind: "goal".to_owned(), args: vec![0.0, 0.0, 10.0, 10.0] };
+        history.execute(&mut level, EditCommand::Batch(vec![
+            EditCommand::AddItem { index: 0, item: block(0.0) },
+            EditCommand::AddEntity { index: 0, entity: goal.clone() },
+        ]));
+        assert_eq!(level.entities, vec![goal]);
+        assert!(history.undo(&mut level));
+        assert!(level.items.is_empty());
+        assert!(level.entities.is_empty());
+    }
+}
@@ -0,0 +1,1135 @@
+mod clipboard;
+mod history;
+mod properties;
+mod selection;
+mod snapping;
+mod tools;
+mod view;
+
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+
+use piston::input::{Button, Key, MouseButton, MouseCursorEvent, MouseScrollEvent, PressEvent,
+                    ReleaseEvent, RenderArgs, TextEvent, UpdateArgs, Input};
+use graphics::{self, Transformed};
+
+use ::{Hitboxes, SettingsChannel};
+use super::{MenuScene, draw_text};
+use super::play::PlayData;
+use super::stack::{Scene, SceneContext, SceneResult, Transition};
+use level_serialization::{Fragment, Level, LevelItem, load_fragment, load_level, save_fragment,
+                          save_level};
+use map::Map;
+
+use self::clipboard::Clipboard;
+use self::history::{EditCommand, History};
+use self::properties::PropertyPanel;
+use self::selection::{HANDLES, Handle};
+use self::snapping::{Edges, Snapping};
+use self::tools::Tool;
+use self::view::{EditorView, PAN_STEP, ZOOM_STEP};
+
+/// Size of resize handles in screen pixels, and how far outside of a block a click can be and
+/// still select it.
+const HANDLE_SIZE: f64 = 6.0;
+const SELECTION_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+const GUIDE_COLOR: [f32; 4] = [1.0, 0.4, 0.2, 1.0];
+const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.3];
+/// Closest grid lines are drawn together on screen, in pixels. Every other line is skipped until
+/// they're at least this far apart.
+const MIN_GRID_SPACING: f64 = 8.0;
+const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.7];
+const PANEL_WIDTH: f64 = 180.0;
+const PANEL_ROW_HEIGHT: f64 = 16.0;
+/// How far Ctrl+D places copies from the originals, until a copy is moved to show a better offset.
+const DUPLICATE_OFFSET: (f64, f64) = (10.0, 10.0);
+
+/// What the left mouse button is currently doing.
+#[derive(Copy, Clone)]
+enum Drag {
+    /// Drawing a new item with the current tool from this corner.
+    Draw { start_x: f64, start_y: f64 },
+    /// Moving the selected items by dragging `anchor`, which was grabbed at the given point.
+    Move { anchor: LevelItem, grab_x: f64, grab_y: f64 },
+    Resize { original: LevelItem, handle: Handle },
+    /// Dragging one side of the level bounds, see `tools::drag_bounds_edge`.
+    BoundsEdge { original: [f64; 4], edge: usize },
+    /// Dragging out new level bounds from this corner.
+    BoundsRect { original: [f64; 4], start_x: f64, start_y: f64 },
+    /// Selecting everything inside the rectangle from this corner.
+    Select { start_x: f64, start_y: f64 },
+}
+
+pub struct EditorScene {
+    session: EditorData,
+}
+
+impl EditorScene {
+    pub fn new<T: AsRef<Path> + Copy>(level_file: T, ctx: &mut SceneContext) -> EditorScene {
+        let mut buf = Vec::new();
+        {
+            let mut file = File::open(level_file).unwrap();
+            file.read_to_end(&mut buf).unwrap();
+        }
+        let map = load_level(&buf)
+            .expect(&format!("Failed to load level: {}", level_file.as_ref().display()));
+        EditorScene {
+            session: EditorData::new(&map, level_file.as_ref(), ctx.cache.hitboxes(), ctx.sc),
+        }
+    }
+}
+
+impl Scene for EditorScene {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        if let Some(Button::Keyboard(Key::Escape)) = event.press_args() {
+            if self.session.cancel(ctx.sc) {
+                return Transition::Stay;
+            }
+            if self.session.history.is_saved() {
+                return Transition::Pop;
+            }
+            let confirm = MenuScene::choice("DISCARD UNSAVED CHANGES?", &["NO", "YES"]);
+            return Transition::Push(Box::new(confirm.overlay()));
+        }
+        self.session.process(event, ctx.sc);
+        Transition::Stay
+    }
+
+    fn resume(&mut self, _ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        // Keys released while the menu was open were never seen.
+        self.session.release_modifiers();
+        match result {
+            Some(SceneResult::Chosen(1)) => Transition::Pop,
+            _ => Transition::Stay,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut SceneContext, args: &UpdateArgs) -> Transition {
+        self.session.update(args, ctx.sc);
+        Transition::Stay
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        self.session.render(ctx, args);
+    }
+}
+
+struct EditorData {
+    play_data: PlayData,
+    /// The level being edited. `play_data.map` is rebuilt from this after every change.
+    level: Level,
+    path: PathBuf,
+    history: History,
+    tool: Tool,
+    view: EditorView,
+    /// Where the player was before play-testing started, while play-testing.
+    play_test: Option<(f64, f64)>,
+    /// Whether the middle mouse button is held down, dragging the view.
+    panning: bool,
+    /// Sorted indices into `level.items`, which match `play_data.map.blocks()`.
+    selected: Vec<usize>,
+    /// The selected items as they were when a move started.
+    moving: Vec<(usize, LevelItem)>,
+    drag: Option<Drag>,
+    /// Set while typing values into the property panel.
+    properties: Option<PropertyPanel>,
+    clipboard: Clipboard,
+    /// Bottom left corner of the items last duplicated, while the copies are still selected.
+    duplicate_source: Option<(f64, f64)>,
+    duplicate_offset: (f64, f64),
+    snapping: Snapping,
+    /// Whether lines are drawn along the snapping grid.
+    show_grid: bool,
+    /// Existing edges currently being snapped to along (x, y), shown as guide lines.
+    guides: (Option<f64>, Option<f64>),
+    ctrl_held: bool,
+    /// Shift-clicking adds and removes items from the selection, and shift-dragging selects a
+    /// whole area.
+    shift_held: bool,
+    /// Snapping is turned off while alt is held.
+    alt_held: bool,
+    current_mouse_x: f64,
+    current_mouse_y: f64,
+    screen_width: f64,
+    screen_height: f64,
+}
+
+impl EditorData {
+    pub fn new(level: &Level, path: &Path, hitboxes: Hitboxes, sc: &SettingsChannel)
+               -> EditorData {
+        EditorData {
+            play_data: PlayData::new(level, hitboxes, sc),
+            level: level.clone(),
+            path: path.to_owned(),
+            history: History::default(),
+            tool: Tool::Box,
+            view: EditorView::new(level.initial_x, level.initial_y),
+            panning: false,
+            play_test: None,
+            selected: Vec::new(),
+            moving: Vec::new(),
+            drag: None,
+            properties: None,
+            clipboard: Clipboard::new(),
+            duplicate_source: None,
+            duplicate_offset: DUPLICATE_OFFSET,
+            snapping: Snapping::default(),
+            show_grid: true,
+            guides: (None, None),
+            ctrl_held: false,
+            shift_held: false,
+            alt_held: false,
+            current_mouse_x: 0f64,
+            current_mouse_y: 0f64,
+            screen_width: 0f64,
+            screen_height: 0f64,
+        }
+    }
+
+    fn execute(&mut self, command: EditCommand) {
+        self.history.execute(&mut self.level, command);
+        self.sync_map();
+    }
+
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.level) {
+            self.set_selection(Vec::new());
+            self.sync_map();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.level) {
+            self.set_selection(Vec::new());
+            self.sync_map();
+        }
+    }
+
+    fn sync_map(&mut self) {
+        self.play_data.map = Map::from(&self.level);
+    }
+
+    fn save(&mut self) {
+        let result = File::create(&self.path)
+            .and_then(|mut file| save_level(&self.level, &mut file));
+        match result {
+            Ok(()) => {
+                self.history.mark_saved();
+                println!("Saved {}", self.path.display());
+            }
+            Err(e) => println!("Failed to save {}: {}", self.path.display(), e),
+        }
+    }
+
+    fn select_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        if !tool.draws_items() {
+            self.set_selection(Vec::new());
+        }
+    }
+
+    /// Replaces the selection with the given sorted indices.
+    fn set_selection(&mut self, selected: Vec<usize>) {
+        self.selected = selected;
+        self.duplicate_source = None;
+    }
+
+    fn selected_items(&self) -> Vec<LevelItem> {
+        self.selected.iter().map(|&index| self.level.items[index]).collect()
+    }
+
+    /// World coordinates of the mouse cursor.
+    fn mouse_world(&self) -> (f64, f64) {
+        self.view.to_world(self.current_mouse_x, self.current_mouse_y)
+    }
+
+    /// `HANDLE_SIZE` in world units, so handles stay the same size on screen at any zoom.
+    fn handle_size(&self) -> f64 {
+        HANDLE_SIZE / self.view.zoom
+    }
+
+    fn active_snapping(&self) -> Snapping {
+        Snapping { enabled: self.snapping.enabled && !self.alt_held, ..self.snapping }
+    }
+
+    /// Edges which can be snapped to, leaving out the items being edited.
+    fn snap_edges(&self, exclude: &[usize]) -> Edges {
+        let mut edges = Edges::default();
+        for (index, item) in self.level.items.iter().enumerate() {
+            if !exclude.contains(&index) {
+                edges.add(item.bounds());
+            }
+        }
+        let bounds = history::bounds(&self.level);
+        edges.add([bounds[0], bounds[1], bounds[2] - bounds[0], bounds[3] - bounds[1]]);
+        edges
+    }
+
+    /// World coordinates of the mouse cursor after snapping, updating the guide lines.
+    fn snapped_mouse(&mut self, exclude: &[usize]) -> (f64, f64) {
+        let (x, y) = self.mouse_world();
+        let (snap_x, snap_y) = self.active_snapping().snap_point(x, y, &self.snap_edges(exclude));
+        self.guides = (snap_x.guide, snap_y.guide);
+        (snap_x.value, snap_y.value)
+    }
+
+    fn start_drag(&mut self) {
+        let (x, y) = self.mouse_world();
+        let handle_size = self.handle_size();
+        let drag = match self.tool {
+            Tool::Start => {
+                let to = self.snapped_mouse(&[]);
+                let from = (self.level.initial_x, self.level.initial_y);
+                self.execute(EditCommand::SetStart { from: from, to: to });
+                return;
+            }
+            Tool::Bounds => {
+                let original = history::bounds(&self.level);
+                match tools::bounds_edge_at(original, x, y, handle_size) {
+                    Some(edge) => Drag::BoundsEdge { original: original, edge: edge },
+                    None => {
+                        let (start_x, start_y) = self.snapped_mouse(&[]);
+                        Drag::BoundsRect { original: original, start_x: start_x, start_y: start_y }
+                    }
+                }
+            }
+            Tool::Box | Tool::NorthLine | Tool::EastLine => {
+                if self.selected.len() == 1 {
+                    let original = self.level.items[self.selected[0]];
+                    let handle = selection::handle_at(original.bounds(), x, y, handle_size);
+                    if let Some(handle) = handle {
+                        self.drag = Some(Drag::Resize { original: original, handle: handle });
+                        return;
+                    }
+                }
+                match self.play_data.map.block_at(x, y, handle_size / 2.0) {
+                    Some(index) if self.shift_held => {
+                        let mut selected = self.selected.clone();
+                        match selected.binary_search(&index) {
+                            Ok(position) => {
+                                selected.remove(position);
+                            }
+                            Err(position) => selected.insert(position, index),
+                        }
+                        self.set_selection(selected);
+                        return;
+                    }
+                    Some(index) => {
+                        if !self.selected.contains(&index) {
+                            self.set_selection(vec![index]);
+                        }
+                        let moving = self.selected.iter()
+                            .map(|&index| (index, self.level.items[index]))
+                            .collect();
+                        self.moving = moving;
+                        Drag::Move { anchor: self.level.items[index], grab_x: x, grab_y: y }
+                    }
+                    None if self.shift_held => Drag::Select { start_x: x, start_y: y },
+                    None => {
+                        self.set_selection(Vec::new());
+                        let (start_x, start_y) = self.snapped_mouse(&[]);
+                        Drag::Draw { start_x: start_x, start_y: start_y }
+                    }
+                }
+            }
+        };
+        self.drag = Some(drag);
+    }
+
+    /// Shows moves and resizes live by changing the level directly. They're recorded into the
+    /// history once the drag finishes.
+    fn update_drag(&mut self) {
+        let (x, y) = self.mouse_world();
+        match self.drag {
+            Some(Drag::Draw { .. }) => {
+                self.snapped_mouse(&[]);
+            }
+            Some(Drag::BoundsEdge { original, edge }) => {
+                let (x, y) = self.snapped_mouse(&[]);
+                history::set_bounds(&mut self.level, tools::drag_bounds_edge(original, edge, x, y));
+                self.sync_map();
+            }
+            Some(Drag::BoundsRect { start_x, start_y, .. }) => {
+                let (x, y) = self.snapped_mouse(&[]);
+                let bounds = tools::normalize_bounds([start_x, start_y, x, y]);
+                history::set_bounds(&mut self.level, bounds);
+                self.sync_map();
+            }
+            Some(Drag::Move { anchor, grab_x, grab_y }) => {
+                let moved = anchor.translated(x - grab_x, y - grab_y).bounds();
+                let edges = self.snap_edges(&self.selected);
+                let (bounds, snap_x, snap_y) = self.active_snapping().snap_rect(moved, &edges);
+                self.guides = (snap_x.guide, snap_y.guide);
+                let dx = bounds[0] - anchor.bounds()[0];
+                let dy = bounds[1] - anchor.bounds()[1];
+                for &(index, original) in &self.moving {
+                    self.level.items[index] = original.translated(dx, dy);
+                }
+                self.sync_map();
+            }
+            Some(Drag::Resize { original, handle }) => {
+                let selected = self.selected.clone();
+                let (x, y) = self.snapped_mouse(&selected);
+                let bounds = selection::resize(original.bounds(), handle, x, y);
+                self.level.items[selected[0]] = original.with_bounds(bounds);
+                self.sync_map();
+            }
+            Some(Drag::Select { .. }) | None => (),
+        }
+    }
+
+    fn finish_drag(&mut self) {
+        let drag = self.drag.take();
+        match drag {
+            Some(Drag::Draw { start_x, start_y }) => {
+                let end = self.snapped_mouse(&[]);
+                if let Some(item) = self.tool.item((start_x, start_y), end) {
+                    let index = self.level.items.len();
+                    self.execute(EditCommand::AddItem { index: index, item: item });
+                    self.set_selection(vec![index]);
+                }
+            }
+            Some(Drag::BoundsEdge { original, .. }) |
+            Some(Drag::BoundsRect { original, .. }) => {
+                let changed = history::bounds(&self.level);
+                if changed != original {
+                    self.history.record(EditCommand::SetBounds { from: original, to: changed });
+                }
+            }
+            Some(Drag::Select { start_x, start_y }) => {
+                let (x, y) = self.mouse_world();
+                let area = [f64::min(start_x, x), f64::min(start_y, y),
+                            f64::abs(x - start_x), f64::abs(y - start_y)];
+                let mut selected = self.selected.clone();
+                for (index, item) in self.level.items.iter().enumerate() {
+                    if selection::contains(area, item.bounds()) {
+                        if let Err(position) = selected.binary_search(&index) {
+                            selected.insert(position, index);
+                        }
+                    }
+                }
+                self.set_selection(selected);
+            }
+            Some(Drag::Move { .. }) => {
+                let moves = self.moving.iter().filter_map(|&(index, original)| {
+                    let moved = self.level.items[index];
+                    if moved.bounds() != original.bounds() {
+                        Some(EditCommand::MoveItem { index: index, from: original, to: moved })
+                    } else {
+                        None
+                    }
+                }).collect::<Vec<_>>();
+                if !moves.is_empty() {
+                    self.history.record(EditCommand::Batch(moves));
+                    // Moving fresh copies shows where the next ones should go.
+                    if let Some((source_x, source_y)) = self.duplicate_source {
+                        if let Some(bounds) = self.selection_bounds() {
+                            self.duplicate_offset = (bounds[0] - source_x, bounds[1] - source_y);
+                        }
+                    }
+                }
+                self.moving.clear();
+            }
+            Some(Drag::Resize { original, .. }) => {
+                let index = self.selected[0];
+                let resized = self.level.items[index];
+                if resized.bounds() != original.bounds() {
+                    self.history.record(EditCommand::ResizeItem {
+                        index: index,
+                        from: original,
+                        to: resized,
+                    });
+                }
+            }
+            None => (),
+        }
+        self.guides = (None, None);
+    }
+
+    fn selection_bounds(&self) -> Option<[f64; 4]> {
+        selection::union(self.selected.iter().map(|&index| self.level.items[index].bounds()))
+    }
+
+    fn delete_selected(&mut self) {
+        let selected = mem::replace(&mut self.selected, Vec::new());
+        // Deleted from the end so earlier indices stay correct.
+        let deletes = {
+            let items = &self.level.items;
+            selected.iter().rev().map(|&index| {
+                EditCommand::DeleteItem { index: index, item: items[index] }
+            }).collect::<Vec<_>>()
+        };
+        if !deletes.is_empty() {
+            self.execute(EditCommand::Batch(deletes));
+        }
+        self.set_selection(Vec::new());
+    }
+
+    /// Copies the selection as `.map` lines, so it can be pasted into other levels or a text
+    /// editor.
+    fn copy_selected(&mut self) {
+        let items = self.selected_items();
+        if items.is_empty() {
+            return;
+        }
+        let fragment = Fragment { items: items, entities: Vec::new() };
+        let mut text = Vec::new();
+        save_fragment(&fragment, &mut text).expect("writing to a Vec can't fail");
+        self.clipboard.set(String::from_utf8_lossy(&text).into_owned());
+    }
+
+    fn cut_selected(&mut self) {
+        self.copy_selected();
+        self.delete_selected();
+    }
+
+    /// Adds the items and entities in the clipboard with their bottom left corner at the mouse.
+    fn paste(&mut self) {
+        let text = self.clipboard.get();
+        match load_fragment(&text) {
+            Ok(fragment) => {
+                let (x, y) = self.snapped_mouse(&[]);
+                self.guides = (None, None);
+                self.add_fragment(&fragment, x, y);
+            }
+            Err(e) => println!("Clipboard doesn't contain level items: {:?}", e),
+        }
+    }
+
+    /// Adds copies of the selection `duplicate_offset` away from the originals.
+    fn duplicate_selected(&mut self) {
+        let items = self.selected_items();
+        if let Some(bounds) = self.selection_bounds() {
+            let (dx, dy) = self.duplicate_offset;
+            let fragment = Fragment { items: items, entities: Vec::new() };
+            self.add_fragment(&fragment, bounds[0] + dx, bounds[1] + dy);
+            self.duplicate_source = Some((bounds[0], bounds[1]));
+        }
+    }
+
+    /// Adds the items and entities moved as a group so their bottom left corner is at (x, y), and
+    /// selects the items.
+    fn add_fragment(&mut self, fragment: &Fragment, x: f64, y: f64) {
+        let item_bounds = fragment.items.iter().map(|item| item.bounds());
+        let entity_points = fragment.entities.iter()
+            .filter_map(|entity| entity.position())
+            .map(|(x, y)| [x, y, 0.0, 0.0]);
+        let bounds = match selection::union(item_bounds.chain(entity_points)) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let (dx, dy) = (x - bounds[0], y - bounds[1]);
+        let first = self.level.items.len();
+        let first_entity = self.level.entities.len();
+        let mut adds = fragment.items.iter().enumerate().map(|(offset, item)| {
+            EditCommand::AddItem { index: first + offset, item: item.translated(dx, dy) }
+        }).collect::<Vec<_>>();
+        adds.extend(fragment.entities.iter().enumerate().map(|(offset, entity)| {
+            EditCommand::AddEntity {
+                index: first_entity + offset,
+                entity: entity.translated(dx, dy),
+            }
+        }));
+        self.execute(EditCommand::Batch(adds));
+        self.set_selection((first..first + fragment.items.len()).collect());
+    }
+
+    fn render_overlay(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let viewport = graphics::Viewport {
+            rect: [0, 0, args.width as i32, args.height as i32],
+            draw_size: [1; 2],
+            window_size: [1; 2],
+        };
+
+        let view = self.view;
+        let handle_size = self.handle_size();
+        let line_width = 1.0 / view.zoom;
+        let screen_width = args.width as f64;
+        let screen_height = args.height as f64;
+        let (mouse_x, mouse_y) = self.mouse_world();
+        let (snap_x, snap_y) = self.active_snapping()
+            .snap_point(mouse_x, mouse_y, &self.snap_edges(&[]));
+        let (mouse_x, mouse_y) = (snap_x.value, snap_y.value);
+        let guides = self.guides;
+        let drawing = match self.drag {
+            Some(Drag::Draw { start_x, start_y }) => {
+                self.tool.item((start_x, start_y), (mouse_x, mouse_y)).map(|item| item.bounds())
+            }
+            _ => None,
+        };
+        let select_area = match self.drag {
+            Some(Drag::Select { start_x, start_y }) => {
+                let (x, y) = self.mouse_world();
+                Some([f64::min(start_x, x), f64::min(start_y, y),
+                      f64::abs(x - start_x), f64::abs(y - start_y)])
+            }
+            _ => None,
+        };
+        let selected = self.selected_items().iter().map(|item| item.bounds()).collect::<Vec<_>>();
+        let (player_width, player_height) = ctx.cache.player.collision_box();
+        let start = [self.level.initial_x, self.level.initial_y,
+                     player_width as f64, player_height as f64];
+        let bounds = history::bounds(&self.level);
+        let bounds = [bounds[0], bounds[1], bounds[2] - bounds[0], bounds[3] - bounds[1]];
+        let tool = self.tool;
+        let grid = if self.show_grid { self.snapping.grid } else { 0.0 };
+
+        ctx.graphics.draw(viewport, |context, graphics| {
+            let world = context.trans(screen_width / 2.0, screen_height / 2.0)
+                .zoom(view.zoom)
+                .trans(-view.x, view.y)
+                .flip_v();
+            let visible = view.visible();
+            if grid > 0.0 {
+                let mut spacing = grid;
+                while spacing * view.zoom < MIN_GRID_SPACING {
+                    spacing *= 2.0;
+                }
+                let line = graphics::Line::new(GRID_COLOR, line_width / 2.0);
+                let mut x = (visible[0] / spacing).floor() * spacing;
+                while x <= visible[0] + visible[2] {
+                    line.draw([x, visible[1], x, visible[1] + visible[3]],
+                              &world.draw_state, world.transform, graphics);
+                    x += spacing;
+                }
+                let mut y = (visible[1] / spacing).floor() * spacing;
+                while y <= visible[1] + visible[3] {
+                    line.draw([visible[0], y, visible[0] + visible[2], y],
+                              &world.draw_state, world.transform, graphics);
+                    y += spacing;
+                }
+            }
+            if let Some(bounds) = drawing {
+                graphics::Rectangle::new(graphics::color::BLACK).draw(
+                    bounds,
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            if let Some(x) = guides.0 {
+                graphics::Line::new(GUIDE_COLOR, line_width / 2.0).draw(
+                    [x, visible[1], x, visible[1] + visible[3]],
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            if let Some(y) = guides.1 {
+                graphics::Line::new(GUIDE_COLOR, line_width / 2.0).draw(
+                    [visible[0], y, visible[0] + visible[2], y],
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            graphics::Rectangle::new_border(SELECTION_COLOR, line_width).draw(
+                start,
+                &world.draw_state,
+                world.transform,
+                graphics,
+            );
+            if tool == Tool::Bounds {
+                graphics::Rectangle::new_border(SELECTION_COLOR, line_width).draw(
+                    bounds,
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            if let Some(area) = select_area {
+                graphics::Rectangle::new_border(GUIDE_COLOR, line_width).draw(
+                    area,
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            for &bounds in &selected {
+                graphics::Rectangle::new_border(SELECTION_COLOR, line_width).draw(
+                    bounds,
+                    &world.draw_state,
+                    world.transform,
+                    graphics,
+                );
+            }
+            if selected.len() == 1 {
+                let bounds = selected[0];
+                for handle in &HANDLES {
+                    graphics::Rectangle::new(SELECTION_COLOR).draw(
+                        handle.rect(bounds, handle_size),
+                        &world.draw_state,
+                        world.transform,
+                        graphics,
+                    );
+                }
+            }
+        })
+    }
+
+    /// Cursor position, the size of what's being drawn or selected, and how many blocks there are.
+    fn hud_text(&self) -> String {
+        let (x, y) = self.mouse_world();
+        let (snap_x, snap_y) = self.active_snapping().snap_point(x, y, &self.snap_edges(&[]));
+        let mut text = format!("X: {:.1}  Y: {:.1}", snap_x.value, snap_y.value);
+        let size = match self.drag {
+            Some(Drag::Draw { start_x, start_y }) => {
+                self.tool.item((start_x, start_y), (snap_x.value, snap_y.value))
+                    .map(|item| item.bounds())
+            }
+            _ => self.selection_bounds(),
+        };
+        if let Some(bounds) = size {
+            text.push_str(&format!("    SIZE: {:.1} x {:.1}", bounds[2], bounds[3]));
+        }
+        if self.selected.len() > 1 {
+            text.push_str(&format!("    SELECTED: {}", self.selected.len()));
+        }
+        text.push_str(&format!("    BLOCKS: {}", self.level.items.len()));
+        text
+    }
+
+    /// Draws lines of text at the top and bottom of the screen.
+    fn render_status(&mut self, ctx: &mut SceneContext, args: &RenderArgs, top: &str,
+                     bottom: &str) {
+        let viewport = graphics::Viewport {
+            rect: [0, 0, args.width as i32, args.height as i32],
+            draw_size: [1; 2],
+            window_size: [1; 2],
+        };
+        let screen_width = args.width as f64;
+        let screen_height = args.height as f64;
+        let cache = &mut *ctx.cache;
+
+        ctx.graphics.draw(viewport, |context, graphics| {
+            draw_text([0.0, 4.0, screen_width, 14.0], top, 12, SELECTION_COLOR,
+                      cache, &context, graphics);
+            draw_text([0.0, screen_height - 18.0, screen_width, 14.0], bottom, 12,
+                      SELECTION_COLOR, cache, &context, graphics);
+        })
+    }
+
+    /// Starts typing in values for the selected item, if exactly one is selected.
+    fn open_properties(&mut self) {
+        if self.selected.len() == 1 {
+            let index = self.selected[0];
+            self.properties = Some(PropertyPanel::new(index, self.level.items[index]));
+        }
+    }
+
+    /// Shows what's been typed into the property panel so far, if it's a valid value.
+    fn update_properties(&mut self) {
+        let edited = match self.properties {
+            Some(ref panel) => {
+                panel.edited(&self.level.items[panel.index]).map(|item| (panel.index, item))
+            }
+            None => None,
+        };
+        if let Some((index, item)) = edited {
+            self.level.items[index] = item;
+            self.sync_map();
+        }
+    }
+
+    fn move_property_field(&mut self, step: isize) {
+        if let Some(ref mut panel) = self.properties {
+            let item = self.level.items[panel.index];
+            panel.move_field(&item, step);
+        }
+    }
+
+    /// Stops typing into the property panel, recording the changes or reverting them.
+    fn close_properties(&mut self, keep: bool) {
+        if let Some(panel) = self.properties.take() {
+            let edited = self.level.items[panel.index];
+            if !keep {
+                self.level.items[panel.index] = panel.original;
+                self.sync_map();
+            } else if edited != panel.original {
+                self.history.record(EditCommand::EditItem {
+                    index: panel.index,
+                    from: panel.original,
+                    to: edited,
+                });
+            }
+        }
+    }
+
+    /// Handles escape, closing the property panel or going back to edit mode. Gives false if
+    /// there was nothing for it to cancel and the editor should be closed.
+    pub fn cancel(&mut self, sc: &SettingsChannel) -> bool {
+        if self.properties.is_some() {
+            self.close_properties(false);
+            true
+        } else if self.play_test.is_some() {
+            self.toggle_play_test(false, sc);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn release_modifiers(&mut self) {
+        self.ctrl_held = false;
+        self.shift_held = false;
+        self.alt_held = false;
+        self.panning = false;
+    }
+
+    fn process_properties(&mut self, event: &Input) {
+        event.text(|typed| {
+            if let Some(ref mut panel) = self.properties {
+                panel.type_text(typed);
+            }
+            self.update_properties();
+        });
+        event.press(|button| {
+            match button {
+                Button::Keyboard(Key::Backspace) => {
+                    if let Some(ref mut panel) = self.properties {
+                        panel.backspace();
+                    }
+                    self.update_properties();
+                }
+                Button::Keyboard(Key::Tab) => {
+                    let step = if self.shift_held { -1 } else { 1 };
+                    self.move_property_field(step);
+                }
+                Button::Keyboard(Key::Down) => self.move_property_field(1),
+                Button::Keyboard(Key::Up) => self.move_property_field(-1),
+                Button::Keyboard(Key::Return) => self.close_properties(true),
+                Button::Mouse(MouseButton::Left) => {
+                    self.close_properties(true);
+                    self.start_drag();
+                }
+                _ => (),
+            }
+        });
+    }
+
+    /// Lists the fields of the selected item in the top right corner of the screen.
+    fn render_properties(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let item = match (&self.properties, self.selected.len()) {
+            (&Some(ref panel), _) => self.level.items[panel.index],
+            (&None, 1) => self.level.items[self.selected[0]],
+            _ => return,
+        };
+        let header = if self.properties.is_some() {
+            "TAB: NEXT  ENTER: DONE"
+        } else {
+            "ENTER: EDIT"
+        };
+        let mut rows = vec![(header.to_owned(), false)];
+        for &field in properties::fields(&item) {
+            match self.properties {
+                Some(ref panel) if panel.field() == field => {
+                    rows.push((format!("{}: {}_", field.name(), panel.text()), true));
+                }
+                _ => {
+                    let value = properties::value(&item, field);
+                    rows.push((format!("{}: {}", field.name(), value), false));
+                }
+            }
+        }
+
+        let viewport = graphics::Viewport {
+            rect: [0, 0, args.width as i32, args.height as i32],
+            draw_size: [1; 2],
+            window_size: [1; 2],
+        };
+        let x_pos = args.width as f64 - PANEL_WIDTH - 10.0;
+        let cache = &mut *ctx.cache;
+
+        ctx.graphics.draw(viewport, |context, graphics| {
+            graphics::Rectangle::new(PANEL_COLOR).draw(
+                [x_pos, 24.0, PANEL_WIDTH, rows.len() as f64 * PANEL_ROW_HEIGHT],
+                &context.draw_state,
+                context.transform,
+                graphics,
+            );
+            for (index, &(ref text, focused)) in rows.iter().enumerate() {
+                let row = [x_pos, 24.0 + index as f64 * PANEL_ROW_HEIGHT, PANEL_WIDTH,
+                           PANEL_ROW_HEIGHT];
+                let color = if focused {
+                    graphics::Rectangle::new(graphics::color::WHITE).draw(
+                        row,
+                        &context.draw_state,
+                        context.transform,
+                        graphics,
+                    );
+                    graphics::color::BLACK
+                } else {
+                    graphics::color::WHITE
+                };
+                draw_text(row, text, 12, color, cache, &context, graphics);
+            }
+        })
+    }
+
+    /// Only moves anything while play-testing.
+    pub fn update(&mut self, args: &UpdateArgs, sc: &mut SettingsChannel) {
+        if self.play_test.is_some() {
+            self.play_data.update(args, sc);
+        }
+    }
+
+    pub fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        self.screen_width = args.width as f64;
+        self.screen_height = args.height as f64;
+        self.view.resize(self.screen_width, self.screen_height);
+
+        if self.play_test.is_some() {
+            self.play_data.render(ctx, args);
+            self.render_status(ctx, args, "PLAY-TEST    P: BACK TO EDITING", "");
+            return;
+        }
+
+        let view = self.view;
+        self.play_data.render_view(ctx, args, view.x, view.y, view.zoom, false);
+        self.render_overlay(ctx, args);
+        let status = format!("TOOL: {} ({:?})    GRID: {}    P: PLAY-TEST",
+                             self.tool.name(), self.tool.shortcut(), self.snapping.grid);
+        let hud = self.hud_text();
+        self.render_status(ctx, args, &status, &hud);
+        self.render_properties(ctx, args);
+    }
+
+    /// Switches between editing, with the player hidden and frozen, and play-testing from the
+    /// level start or the mouse cursor. Stopping puts the player back where it was.
+    fn toggle_play_test(&mut self, from_cursor: bool, sc: &SettingsChannel) {
+        self.panning = false;
+        match self.play_test.take() {
+            Some((x, y)) => self.play_data.restart(&self.level, x, y, sc),
+            None => {
+                if self.drag.is_some() {
+                    self.finish_drag();
+                }
+                let (x, y) = if from_cursor {
+                    self.mouse_world()
+                } else {
+                    (self.level.initial_x, self.level.initial_y)
+                };
+                self.play_test = Some((self.play_data.player.state.absolute_x,
+                                       self.play_data.player.state.absolute_y));
+                self.play_data.restart(&self.level, x, y, sc);
+            }
+        }
+    }
+
+    pub fn process(&mut self, event: &Input, sc: &SettingsChannel) {
+        event.press(|button| {
+            match button {
+                Button::Keyboard(Key::LCtrl) | Button::Keyboard(Key::RCtrl) => {
+                    self.ctrl_held = true;
+                }
+                Button::Keyboard(Key::LShift) | Button::Keyboard(Key::RShift) => {
+                    self.shift_held = true;
+                }
+                Button::Keyboard(Key::LAlt) | Button::Keyboard(Key::RAlt) => {
+                    self.alt_held = true;
+                }
+                Button::Keyboard(Key::P) if self.properties.is_none() => {
+                    let from_cursor = self.ctrl_held;
+                    self.toggle_play_test(from_cursor, sc);
+                }
+                _ => (),
+            }
+        });
+        event.release(|button| {
+            match button {
+                Button::Keyboard(Key::LCtrl) | Button::Keyboard(Key::RCtrl) => {
+                    self.ctrl_held = false;
+                }
+                Button::Keyboard(Key::LShift) | Button::Keyboard(Key::RShift) => {
+                    self.shift_held = false;
+                }
+                Button::Keyboard(Key::LAlt) | Button::Keyboard(Key::RAlt) => {
+                    self.alt_held = false;
+                }
+                _ => (),
+            }
+        });
+        event.mouse_cursor(|x, y| {
+            let y = self.screen_height - y;
+            if self.panning {
+                self.view.pan(x - self.current_mouse_x, y - self.current_mouse_y);
+            }
+            self.current_mouse_x = x;
+            self.current_mouse_y = y;
+            if self.play_test.is_none() {
+                self.update_drag();
+            }
+        });
+
+        if self.play_test.is_some() {
+            self.play_data.input(event);
+            return;
+        }
+        if self.properties.is_some() {
+            self.process_properties(event);
+            return;
+        }
+        event.mouse_scroll(|_, dy| {
+            let (x, y) = (self.current_mouse_x, self.current_mouse_y);
+            self.view.zoom_at(ZOOM_STEP.powf(dy), x, y);
+            self.update_drag();
+        });
+        event.press(|button| {
+            // Most keys change the items or selection a drag holds indices into.
+            if let Button::Keyboard(key) = button {
+                if self.drag.is_some() && !keeps_drag(key) {
+                    self.finish_drag();
+                }
+            }
+            match button {
+                Button::Mouse(MouseButton::Left) => self.start_drag(),
+                Button::Mouse(MouseButton::Middle) => self.panning = true,
+                Button::Keyboard(Key::Left) => self.view.pan(PAN_STEP, 0.0),
+                Button::Keyboard(Key::Right) => self.view.pan(-PAN_STEP, 0.0),
+                Button::Keyboard(Key::Up) => self.view.pan(0.0, -PAN_STEP),
+                Button::Keyboard(Key::Down) => self.view.pan(0.0, PAN_STEP),
+                Button::Keyboard(Key::F) => {
+                    let bounds = history::bounds(&self.level);
+                    self.view.fit(bounds);
+                }
+                Button::Keyboard(Key::Z) if self.ctrl_held => self.undo(),
+                Button::Keyboard(Key::Y) if self.ctrl_held => self.redo(),
+                Button::Keyboard(Key::S) if self.ctrl_held => self.save(),
+                Button::Keyboard(Key::C) if self.ctrl_held => self.copy_selected(),
+                Button::Keyboard(Key::X) if self.ctrl_held => self.cut_selected(),
+                Button::Keyboard(Key::V) if self.ctrl_held => self.paste(),
+                Button::Keyboard(Key::D) if self.ctrl_held => self.duplicate_selected(),
+                Button::Keyboard(Key::A) if self.ctrl_held => {
+                    let all = (0..self.level.items.len()).collect();
+                    self.set_selection(all);
+                }
+                Button::Keyboard(Key::Delete) => self.delete_selected(),
+                Button::Keyboard(Key::Return) => self.open_properties(),
+                Button::Keyboard(Key::G) if self.ctrl_held => self.show_grid = !self.show_grid,
+                Button::Keyboard(Key::G) => self.snapping.cycle_grid(),
+                Button::Keyboard(key) if !self.ctrl_held => {
+                    if let Some(tool) = Tool::from_shortcut(key) {
+                        self.select_tool(tool);
+                    }
+                }
+                _ => (),
+            }
+        });
+        event.release(|button| {
+            match button {
+                Button::Mouse(MouseButton::Left) => self.finish_drag(),
+                Button::Mouse(MouseButton::Middle) => self.panning = false,
+                _ => (),
+            }
+        });
+    }
+}
+
+/// Whether the key can be used during a drag, since it leaves the level and selection alone.
+fn keeps_drag(key: Key) -> bool {
+    match key {
+        Key::LCtrl | Key::RCtrl | Key::LShift | Key::RShift | Key::LAlt | Key::RAlt |
+        Key::Left | Key::Right | Key::Up | Key::Down | Key::F | Key::G | Key::S | Key::C => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    use piston::input::{Button, Input, Key, MouseButton, Motion};
+
+    use ::{Hitboxes, SettingsChannel};
+    use level_serialization::{Level, LevelItem};
+    use mechanics::PlayerSettings;
+
+    use super::{Drag, EditorData};
+    use super::view::EditorView;
+
+    fn block(x: f64) -> LevelItem {
+        LevelItem::Box { x: x, y: 0.0, width: 20.0, height: 20.0 }
+    }
+
+    fn settings_channel() -> SettingsChannel {
+        let (send, recv) = mpsc::channel();
+        let (telemetry, _) = mpsc::channel();
+        SettingsChannel::new(send, recv, telemetry, PlayerSettings::default())
+    }
+
+    /// An editor with the view placed so screen and world coordinates are the same.
+    fn editor(items: &[LevelItem], sc: &SettingsChannel) -> EditorData {
+        let mut level = Level::empty();
+        level.items = items.to_vec();
+        let hitboxes = Hitboxes { player: (10, 20), patroller: (10, 20) };
+        let mut editor = EditorData::new(&level, Path::new("test.map"), hitboxes, sc);
+        editor.view = EditorView::new(0.0, 0.0);
+        editor.snapping.enabled = false;
+        editor
+    }
+
+    fn send(editor: &mut EditorData, sc: &SettingsChannel, input: Input) {
+        editor.process(&input, sc);
+    }
+
+    /// Moves the mouse to world (x, y). Cursor events have y going down from the top.
+    fn move_to(editor: &mut EditorData, sc: &SettingsChannel, x: f64, y: f64) {
+        send(editor, sc, Input::Move(Motion::MouseCursor(x, -y)));
+    }
+
+    fn press(editor: &mut EditorData, sc: &SettingsChannel, button: Button) {
+        send(editor, sc, Input::Press(button));
+    }
+
+    fn release(editor: &mut EditorData, sc: &SettingsChannel, button: Button) {
+        send(editor, sc, Input::Release(button));
+    }
+
+    #[test]
+    fn delete_during_move_finishes_it_first() {
+        let sc = settings_channel();
+        let mut editor = editor(&[block(0.0), block(100.0)], &sc);
+        move_to(&mut editor, &sc, 110.0, 10.0);
+        press(&mut editor, &sc, Button::Mouse(MouseButton::Left));
+        match editor.drag {
+            Some(Drag::Move { .. }) => (),
+            _ => panic!("expected to be moving the block"),
+        }
+        move_to(&mut editor, &sc, 130.0, 10.0);
+        press(&mut editor, &sc, Button::Keyboard(Key::Delete));
+        move_to(&mut editor, &sc, 150.0, 10.0);
+        release(&mut editor, &sc, Button::Mouse(MouseButton::Left));
+
+        assert!(editor.drag.is_none());
+        assert_eq!(editor.level.items, vec![block(0.0)]);
+        // Undoing the delete gives back the block where the move left it.
+        assert!(editor.history.undo(&mut editor.level));
+        assert_eq!(editor.level.items, vec![block(0.0), block(120.0)]);
+    }
+
+    #[test]
+    fn undo_during_resize_finishes_it_first() {
+        let sc = settings_channel();
+        let mut editor = editor(&[block(0.0)], &sc);
+        editor.set_selection(vec![0]);
+        // The top right corner handle.
+        move_to(&mut editor, &sc, 20.0, 20.0);
+        press(&mut editor, &sc, Button::Mouse(MouseButton::Left));
+        match editor.drag {
+            Some(Drag::Resize { .. }) => (),
+            _ => panic!("expected to be resizing the block"),
+        }
+        move_to(&mut editor, &sc, 40.0, 20.0);
+        press(&mut editor, &sc, Button::Keyboard(Key::LCtrl));
+        press(&mut editor, &sc, Button::Keyboard(Key::Z));
+        press(&mut editor, &sc, Button::Keyboard(Key::Delete));
+        move_to(&mut editor, &sc, 60.0, 20.0);
+        release(&mut editor, &sc, Button::Mouse(MouseButton::Left));
+
+        assert!(editor.drag.is_none());
+        assert!(editor.level.items.is_empty());
+    }
+}
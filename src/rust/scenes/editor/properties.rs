@@ -0,0 +1,152 @@
+use level_serialization::{Direction, LevelItem};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    X,
+    Y,
+    Width,
+    Height,
+    Direction,
+    Length,
+}
+
+const BOX_FIELDS: [Field; 4] = [Field::X, Field::Y, Field::Width, Field::Height];
+const LINE_FIELDS: [Field; 4] = [Field::X, Field::Y, Field::Direction, Field::Length];
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Field::X => "X",
+            Field::Y => "Y",
+            Field::Width => "WIDTH",
+            Field::Height => "HEIGHT",
+            Field::Direction => "DIRECTION",
+            Field::Length => "LENGTH",
+        }
+    }
+}
+
+pub fn fields(item: &LevelItem) -> &'static [Field] {
+    match *item {
+        LevelItem::Box { .. } => &BOX_FIELDS,
+        LevelItem::Line { .. } => &LINE_FIELDS,
+    }
+}
+
+/// The field's value as it's written in level files.
+pub fn value(item: &LevelItem, field: Field) -> String {
+    match (*item, field) {
+        (LevelItem::Box { x, .. }, Field::X) |
+        (LevelItem::Line { x, .. }, Field::X) => format!("{}", x),
+        (LevelItem::Box { y, .. }, Field::Y) |
+        (LevelItem::Line { y, .. }, Field::Y) => format!("{}", y),
+        (LevelItem::Box { width, .. }, Field::Width) => format!("{}", width),
+        (LevelItem::Box { height, .. }, Field::Height) => format!("{}", height),
+        (LevelItem::Line { direction, .. }, Field::Direction) => format!("{}", direction),
+        (LevelItem::Line { length, .. }, Field::Length) => format!("{}", length),
+        _ => String::new(),
+    }
+}
+
+/// Gives the item with the field parsed from `text`, or None if it isn't a valid value.
+pub fn with_value(item: &LevelItem, field: Field, text: &str) -> Option<LevelItem> {
+    let text = text.trim();
+    if field == Field::Direction {
+        let direction = match &*text.to_lowercase() {
+            "n" | "north" => Direction::North,
+            "e" | "east" => Direction::East,
+            _ => return None,
+        };
+        return match *item {
+            LevelItem::Line { x, y, length, .. } => {
+                Some(LevelItem::Line { x: x, y: y, direction: direction, length: length })
+            }
+            LevelItem::Box { .. } => None,
+        };
+    }
+
+    let number = match text.parse::<f64>() {
+        Ok(number) if number.is_finite() => number,
+        _ => return None,
+    };
+    // Sizes have to stay positive, positions can be anything.
+    if field != Field::X && field != Field::Y && number <= 0.0 {
+        return None;
+    }
+    let edited = match *item {
+        LevelItem::Box { x, y, width, height } => {
+            match field {
+                Field::X => LevelItem::Box { x: number, y: y, width: width, height: height },
+                Field::Y => LevelItem::Box { x: x, y: number, width: width, height: height },
+                Field::Width => LevelItem::Box { x: x, y: y, width: number, height: height },
+                Field::Height => LevelItem::Box { x: x, y: y, width: width, height: number },
+                _ => return None,
+            }
+        }
+        LevelItem::Line { x, y, direction, length } => {
+            match field {
+                Field::X => {
+                    LevelItem::Line { x: number, y: y, direction: direction, length: length }
+                }
+                Field::Y => {
+                    LevelItem::Line { x: x, y: number, direction: direction, length: length }
+                }
+                Field::Length => {
+                    LevelItem::Line { x: x, y: y, direction: direction, length: number }
+                }
+                _ => return None,
+            }
+        }
+    };
+    Some(edited)
+}
+
+/// Typing in exact values for one item's fields.
+pub struct PropertyPanel {
+    /// Index into `level.items`.
+    pub index: usize,
+    /// The item as it was when editing started, to go back to if it's cancelled.
+    pub original: LevelItem,
+    field: usize,
+    text: String,
+}
+
+impl PropertyPanel {
+    pub fn new(index: usize, item: LevelItem) -> PropertyPanel {
+        PropertyPanel {
+            index: index,
+            original: item,
+            field: 0,
+            text: value(&item, fields(&item)[0]),
+        }
+    }
+
+    pub fn field(&self) -> Field {
+        fields(&self.original)[self.field]
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Moves `step` fields forward or backward, wrapping around, and starts editing the value
+    /// `item` has there.
+    pub fn move_field(&mut self, item: &LevelItem, step: isize) {
+        let count = fields(&self.original).len() as isize;
+        self.field = ((self.field as isize + step) % count + count) as usize % count as usize;
+        self.text = value(item, self.field());
+    }
+
+    pub fn type_text(&mut self, typed: &str) {
+        self.text.extend(typed.chars().filter(|c| !c.is_control()));
+    }
+
+    pub fn backspace(&mut self) {
+        self.text.pop();
+    }
+
+    /// `item` with the typed text applied, if it's valid.
+    pub fn edited(&self, item: &LevelItem) -> Option<LevelItem> {
+        with_value(item, self.field(), &self.text)
+    }
+}
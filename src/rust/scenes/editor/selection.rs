@@ -0,0 +1,77 @@
+use std::f64;
+
+/// Corners of a selected item which can be dragged to resize it. North is towards +y.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handle {
+    SouthWest,
+    SouthEast,
+    NorthWest,
+    NorthEast,
+}
+
+pub const HANDLES: [Handle; 4] =
+    [Handle::SouthWest, Handle::SouthEast, Handle::NorthWest, Handle::NorthEast];
+
+impl Handle {
+    /// Position of this corner of an `[x, y, width, height]` rectangle.
+    pub fn position(&self, bounds: [f64; 4]) -> (f64, f64) {
+        match *self {
+            Handle::SouthWest => (bounds[0], bounds[1]),
+            Handle::SouthEast => (bounds[0] + bounds[2], bounds[1]),
+            Handle::NorthWest => (bounds[0], bounds[1] + bounds[3]),
+            Handle::NorthEast => (bounds[0] + bounds[2], bounds[1] + bounds[3]),
+        }
+    }
+
+    pub fn opposite(&self) -> Handle {
+        match *self {
+            Handle::SouthWest => Handle::NorthEast,
+            Handle::SouthEast => Handle::NorthWest,
+            Handle::NorthWest => Handle::SouthEast,
+            Handle::NorthEast => Handle::SouthWest,
+        }
+    }
+
+    /// `[x, y, width, height]` square drawn for this handle.
+    pub fn rect(&self, bounds: [f64; 4], size: f64) -> [f64; 4] {
+        let (x, y) = self.position(bounds);
+        [x - size / 2.0, y - size / 2.0, size, size]
+    }
+}
+
+/// Finds the handle of `bounds` under the point, if any.
+pub fn handle_at(bounds: [f64; 4], x: f64, y: f64, size: f64) -> Option<Handle> {
+    HANDLES.iter().cloned().find(|handle| {
+        let (handle_x, handle_y) = handle.position(bounds);
+        f64::abs(handle_x - x) <= size / 2.0 && f64::abs(handle_y - y) <= size / 2.0
+    })
+}
+
+/// Gives `original` with the corner `handle` dragged to the point, keeping the opposite corner
+/// where it was.
+pub fn resize(original: [f64; 4], handle: Handle, x: f64, y: f64) -> [f64; 4] {
+    let (fixed_x, fixed_y) = handle.opposite().position(original);
+    [f64::min(fixed_x, x), f64::min(fixed_y, y), f64::abs(x - fixed_x), f64::abs(y - fixed_y)]
+}
+
+/// Whether the `[x, y, width, height]` rectangle `inner` lies entirely within `outer`.
+pub fn contains(outer: [f64; 4], inner: [f64; 4]) -> bool {
+    inner[0] >= outer[0] && inner[1] >= outer[1] &&
+    inner[0] + inner[2] <= outer[0] + outer[2] && inner[1] + inner[3] <= outer[1] + outer[3]
+}
+
+/// Smallest rectangle containing all of the given rectangles.
+pub fn union<I: IntoIterator<Item = [f64; 4]>>(rects: I) -> Option<[f64; 4]> {
+    rects.into_iter().fold(None, |acc, rect| {
+        Some(match acc {
+            None => rect,
+            Some(acc) => {
+                let min_x = f64::min(acc[0], rect[0]);
+                let min_y = f64::min(acc[1], rect[1]);
+                let max_x = f64::max(acc[0] + acc[2], rect[0] + rect[2]);
+                let max_y = f64::max(acc[1] + acc[3], rect[1] + rect[3]);
+                [min_x, min_y, max_x - min_x, max_y - min_y]
+            }
+        })
+    })
+}
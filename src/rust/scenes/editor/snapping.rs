@@ -0,0 +1,192 @@
+use std::cmp::Ordering;
+use std::f64;
+
+/// Grid sizes cycled through in the editor, 0 being no grid.
+pub const GRID_SIZES: [f64; 5] = [0.0, 5.0, 10.0, 20.0, 50.0];
+/// How close, in world units, a point has to be to an existing edge to snap to it.
+pub const EDGE_THRESHOLD: f64 = 6.0;
+
+/// The result of snapping a value along one axis.
+#[derive(Copy, Clone, Debug)]
+pub struct Snap {
+    pub value: f64,
+    /// Set to the edge snapped to, if the value was snapped to an existing edge rather than the
+    /// grid, for drawing a guide line.
+    pub guide: Option<f64>,
+}
+
+/// Edges of existing blocks and the level bounds, along each axis.
+#[derive(Default)]
+pub struct Edges {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+impl Edges {
+    /// Adds both edges of an `[x, y, width, height]` rectangle along each axis.
+    pub fn add(&mut self, bounds: [f64; 4]) {
+        self.x.push(bounds[0]);
+        self.x.push(bounds[0] + bounds[2]);
+        self.y.push(bounds[1]);
+        self.y.push(bounds[1] + bounds[3]);
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Snapping {
+    /// 0 to disable
+    pub grid: f64,
+    pub enabled: bool,
+}
+
+impl Default for Snapping {
+    fn default() -> Snapping {
+        Snapping {
+            grid: GRID_SIZES[2],
+            enabled: true,
+        }
+    }
+}
+
+impl Snapping {
+    /// Moves to the next grid size, wrapping around to no grid.
+    pub fn cycle_grid(&mut self) {
+        let current = GRID_SIZES.iter().position(|&size| size == self.grid).unwrap_or(0);
+        self.grid = GRID_SIZES[(current + 1) % GRID_SIZES.len()];
+    }
+
+    pub fn snap_point(&self, x: f64, y: f64, edges: &Edges) -> (Snap, Snap) {
+        (self.snap_axis(x, &edges.x), self.snap_axis(y, &edges.y))
+    }
+
+    /// Snaps a rectangle being moved, keeping its size. Either side can snap to an edge, with
+    /// the closer one winning; otherwise the lower corner snaps to the grid.
+    pub fn snap_rect(&self, bounds: [f64; 4], edges: &Edges) -> ([f64; 4], Snap, Snap) {
+        let x = self.snap_span(bounds[0], bounds[2], &edges.x);
+        let y = self.snap_span(bounds[1], bounds[3], &edges.y);
+        ([x.value, y.value, bounds[2], bounds[3]], x, y)
+    }
+
+    fn snap_axis(&self, value: f64, edges: &[f64]) -> Snap {
+        if !self.enabled {
+            return Snap { value: value, guide: None };
+        }
+        match nearest_edge(value, edges) {
+            Some(edge) => Snap { value: edge, guide: Some(edge) },
+            None => Snap { value: self.snap_to_grid(value), guide: None },
+        }
+    }
+
+    /// Snaps the start of a span so either its start or end lies on an edge.
+    fn snap_span(&self, start: f64, length: f64, edges: &[f64]) -> Snap {
+        if !self.enabled {
+            return Snap { value: start, guide: None };
+        }
+        let by_start = nearest_edge(start, edges).map(|edge| (edge - start, edge));
+        let by_end = nearest_edge(start + length, edges).map(|edge| (edge - start - length, edge));
+        let best = match (by_start, by_end) {
+            (Some(a), Some(b)) => Some(if f64::abs(a.0) <= f64::abs(b.0) { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        match best {
+            Some((offset, edge)) => Snap { value: start + offset, guide: Some(edge) },
+            None => Snap { value: self.snap_to_grid(start), guide: None },
+        }
+    }
+
+    fn snap_to_grid(&self, value: f64) -> f64 {
+        if self.grid > 0.0 {
+            (value / self.grid).round() * self.grid
+        } else {
+            value
+        }
+    }
+}
+
+fn nearest_edge(value: f64, edges: &[f64]) -> Option<f64> {
+    edges.iter()
+        .cloned()
+        .filter(|edge| f64::abs(edge - value) <= EDGE_THRESHOLD)
+        .min_by(|a, b| {
+            f64::abs(a - value).partial_cmp(&f64::abs(b - value)).unwrap_or(Ordering::Equal)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edges, EDGE_THRESHOLD, Snapping};
+
+    fn snapping(grid: f64) -> Snapping {
+        Snapping {
+            grid: grid,
+            enabled: true,
+        }
+    }
+
+    fn edges(x: &[f64]) -> Edges {
+        Edges {
+            x: x.to_vec(),
+            y: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rounds_to_grid() {
+        let snapping = snapping(10.0);
+        let none = Edges::default();
+        assert_eq!(snapping.snap_point(14.0, 16.0, &none).0.value, 10.0);
+        assert_eq!(snapping.snap_point(14.0, 16.0, &none).1.value, 20.0);
+        assert_eq!(snapping.snap_point(-14.0, -16.0, &none).0.value, -10.0);
+        assert_eq!(snapping.snap_point(-14.0, -16.0, &none).1.value, -20.0);
+        assert!(snapping.snap_point(14.0, 16.0, &none).0.guide.is_none());
+    }
+
+    #[test]
+    fn no_grid_or_disabled_leaves_values() {
+        let none = Edges::default();
+        assert_eq!(snapping(0.0).snap_point(14.3, 0.0, &none).0.value, 14.3);
+        let disabled = Snapping { enabled: false, ..snapping(10.0) };
+        assert_eq!(disabled.snap_point(14.3, 0.0, &edges(&[15.0])).0.value, 14.3);
+    }
+
+    #[test]
+    fn snaps_to_edges_within_threshold() {
+        let snapping = snapping(10.0);
+        let near = snapping.snap_point(17.0, 0.0, &edges(&[17.0 + EDGE_THRESHOLD]));
+        assert_eq!(near.0.value, 17.0 + EDGE_THRESHOLD);
+        assert_eq!(near.0.guide, Some(17.0 + EDGE_THRESHOLD));
+        let far = snapping.snap_point(17.0, 0.0, &edges(&[17.5 + EDGE_THRESHOLD]));
+        assert_eq!(far.0.value, 20.0);
+        assert!(far.0.guide.is_none());
+    }
+
+    #[test]
+    fn snaps_to_nearest_edge() {
+        let snapping = snapping(10.0);
+        assert_eq!(snapping.snap_point(-12.0, 0.0, &edges(&[-16.0, -11.0, -9.0])).0.value, -11.0);
+        // Ties go to the first edge listed.
+        assert_eq!(snapping.snap_point(3.0, 0.0, &edges(&[1.0, 5.0])).0.value, 1.0);
+        assert_eq!(snapping.snap_point(3.0, 0.0, &edges(&[5.0, 1.0])).0.value, 5.0);
+    }
+
+    #[test]
+    fn rect_snaps_closer_side() {
+        let snapping = snapping(10.0);
+        // The end is 1 away from 50, the start 3 away from 0.
+        let (bounds, x, _) = snapping.snap_rect([3.0, 0.0, 46.0, 10.0], &edges(&[0.0, 50.0]));
+        assert_eq!(bounds, [4.0, 0.0, 46.0, 10.0]);
+        assert_eq!(x.guide, Some(50.0));
+        let (bounds, x, _) = snapping.snap_rect([-23.0, 0.0, 5.0, 10.0], &Edges::default());
+        assert_eq!(bounds[0], -20.0);
+        assert!(x.guide.is_none());
+    }
+
+    #[test]
+    fn cycles_grid_sizes() {
+        let mut snapping = snapping(50.0);
+        snapping.cycle_grid();
+        assert_eq!(snapping.grid, 0.0);
+        snapping.cycle_grid();
+        assert_eq!(snapping.grid, 5.0);
+    }
+}
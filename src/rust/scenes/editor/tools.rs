@@ -0,0 +1,104 @@
+use std::f64;
+
+use piston::input::Key;
+
+use level_serialization::{Direction, LevelItem};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tool {
+    /// Draws boxes, and selects, moves and resizes any existing item.
+    Box,
+    NorthLine,
+    EastLine,
+    /// Sets where the player starts.
+    Start,
+    /// Drags the level bounds' edges, or drags out new bounds.
+    Bounds,
+}
+
+pub const TOOLS: [Tool; 5] =
+    [Tool::Box, Tool::NorthLine, Tool::EastLine, Tool::Start, Tool::Bounds];
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tool::Box => "BOX",
+            Tool::NorthLine => "NORTH LINE",
+            Tool::EastLine => "EAST LINE",
+            Tool::Start => "START",
+            Tool::Bounds => "BOUNDS",
+        }
+    }
+
+    pub fn shortcut(&self) -> Key {
+        match *self {
+            Tool::Box => Key::B,
+            Tool::NorthLine => Key::N,
+            Tool::EastLine => Key::E,
+            Tool::Start => Key::S,
+            Tool::Bounds => Key::L,
+        }
+    }
+
+    pub fn from_shortcut(key: Key) -> Option<Tool> {
+        TOOLS.iter().cloned().find(|tool| tool.shortcut() == key)
+    }
+
+    /// Whether dragging with this tool on an existing item selects it rather than drawing.
+    pub fn draws_items(&self) -> bool {
+        match *self {
+            Tool::Box | Tool::NorthLine | Tool::EastLine => true,
+            Tool::Start | Tool::Bounds => false,
+        }
+    }
+
+    /// The item drawn by dragging from one point to another, if it isn't empty.
+    pub fn item(&self, (start_x, start_y): (f64, f64), (end_x, end_y): (f64, f64))
+                -> Option<LevelItem> {
+        let min_x = f64::min(start_x, end_x);
+        let min_y = f64::min(start_y, end_y);
+        let len_x = f64::abs(end_x - start_x);
+        let len_y = f64::abs(end_y - start_y);
+        let item = match *self {
+            Tool::Box if len_x > 0.0 && len_y > 0.0 => {
+                LevelItem::Box { x: min_x, y: min_y, width: len_x, height: len_y }
+            }
+            Tool::NorthLine if len_y > 0.0 => {
+                LevelItem::Line { x: start_x, y: min_y, direction: Direction::North, length: len_y }
+            }
+            Tool::EastLine if len_x > 0.0 => {
+                LevelItem::Line { x: min_x, y: start_y, direction: Direction::East, length: len_x }
+            }
+            _ => return None,
+        };
+        Some(item)
+    }
+}
+
+/// Bounds with each side dragged to the point, 0 to 3 being west, south, east and north.
+pub fn drag_bounds_edge(original: [f64; 4], edge: usize, x: f64, y: f64) -> [f64; 4] {
+    let mut bounds = original;
+    bounds[edge] = if edge % 2 == 0 { x } else { y };
+    normalize_bounds(bounds)
+}
+
+/// Finds the side of `[west, south, east, north]` bounds within `distance` of the point.
+pub fn bounds_edge_at(bounds: [f64; 4], x: f64, y: f64, distance: f64) -> Option<usize> {
+    let within_x = x >= bounds[0] - distance && x <= bounds[2] + distance;
+    let within_y = y >= bounds[1] - distance && y <= bounds[3] + distance;
+    (0..4).find(|&edge| {
+        if edge % 2 == 0 {
+            within_y && f64::abs(bounds[edge] - x) <= distance
+        } else {
+            within_x && f64::abs(bounds[edge] - y) <= distance
+        }
+    })
+}
+
+/// Swaps sides so west is left of east and south is below north.
+pub fn normalize_bounds(bounds: [f64; 4]) -> [f64; 4] {
+    [f64::min(bounds[0], bounds[2]),
+     f64::min(bounds[1], bounds[3]),
+     f64::max(bounds[0], bounds[2]),
+     f64::max(bounds[1], bounds[3])]
+}
@@ -0,0 +1,141 @@
+use std::f64;
+
+pub const MIN_ZOOM: f64 = 0.1;
+pub const MAX_ZOOM: f64 = 8.0;
+/// Zoom multiplier for each mouse wheel notch.
+pub const ZOOM_STEP: f64 = 1.2;
+/// Screen pixels panned by each arrow key press.
+pub const PAN_STEP: f64 = 50.0;
+/// Screen pixels left around the level when fitting it to the screen.
+pub const FIT_MARGIN: f64 = 20.0;
+
+/// The editor's camera, which moves independently of the player.
+#[derive(Copy, Clone, Debug)]
+pub struct EditorView {
+    /// World coordinates of the center of the screen.
+    pub x: f64,
+    pub y: f64,
+    /// Screen pixels per world unit.
+    pub zoom: f64,
+    screen_width: f64,
+    screen_height: f64,
+}
+
+impl EditorView {
+    pub fn new(x: f64, y: f64) -> EditorView {
+        EditorView {
+            x: x,
+            y: y,
+            zoom: 1.0,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+
+    pub fn resize(&mut self, screen_width: f64, screen_height: f64) {
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+    }
+
+    /// Converts screen coordinates, with y going up from the bottom of the window, to world
+    /// coordinates.
+    pub fn to_world(&self, screen_x: f64, screen_y: f64) -> (f64, f64) {
+        (self.x + (screen_x - self.screen_width / 2.0) / self.zoom,
+         self.y + (screen_y - self.screen_height / 2.0) / self.zoom)
+    }
+
+    /// [x, y, width, height] of the visible area in world coordinates.
+    pub fn visible(&self) -> [f64; 4] {
+        let (min_x, min_y) = self.to_world(0.0, 0.0);
+        [min_x, min_y, self.screen_width / self.zoom, self.screen_height / self.zoom]
+    }
+
+    /// Moves the view so the world follows the mouse being dragged by the given screen distance.
+    pub fn pan(&mut self, screen_dx: f64, screen_dy: f64) {
+        self.x -= screen_dx / self.zoom;
+        self.y -= screen_dy / self.zoom;
+    }
+
+    /// Zooms by `factor`, keeping the world point under the given screen position in place.
+    pub fn zoom_at(&mut self, factor: f64, screen_x: f64, screen_y: f64) {
+        let (world_x, world_y) = self.to_world(screen_x, screen_y);
+        self.zoom = f64::max(MIN_ZOOM, f64::min(MAX_ZOOM, self.zoom * factor));
+        let (new_x, new_y) = self.to_world(screen_x, screen_y);
+        self.x += world_x - new_x;
+        self.y += world_y - new_y;
+    }
+
+    /// Centers and zooms to show all of `[west, south, east, north]` bounds.
+    pub fn fit(&mut self, bounds: [f64; 4]) {
+        let width = bounds[2] - bounds[0];
+        let height = bounds[3] - bounds[1];
+        self.x = bounds[0] + width / 2.0;
+        self.y = bounds[1] + height / 2.0;
+        if width > 0.0 && height > 0.0 {
+            let zoom = f64::min((self.screen_width - FIT_MARGIN * 2.0) / width,
+                                (self.screen_height - FIT_MARGIN * 2.0) / height);
+            self.zoom = f64::max(MIN_ZOOM, f64::min(MAX_ZOOM, zoom));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditorView, FIT_MARGIN, MAX_ZOOM, MIN_ZOOM};
+
+    fn view() -> EditorView {
+        let mut view = EditorView::new(30.0, -40.0);
+        view.resize(800.0, 600.0);
+        view
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn zoom_keeps_cursor_point_fixed() {
+        let mut view = view();
+        for &(factor, screen_x, screen_y) in &[(1.2, 100.0, 500.0), (0.5, 750.0, 20.0)] {
+            let before = view.to_world(screen_x, screen_y);
+            view.zoom_at(factor, screen_x, screen_y);
+            let after = view.to_world(screen_x, screen_y);
+            assert_close(before.0, after.0);
+            assert_close(before.1, after.1);
+        }
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut view = view();
+        view.zoom_at(1000.0, 400.0, 300.0);
+        assert_eq!(view.zoom, MAX_ZOOM);
+        view.zoom_at(0.00001, 400.0, 300.0);
+        assert_eq!(view.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn fit_contains_bounds() {
+        for &bounds in &[[-200.0, -200.0, 1000.0, 1000.0], [10.0, 20.0, 60.0, 30.0]] {
+            let mut view = view();
+            view.fit(bounds);
+            let visible = view.visible();
+            assert!(visible[0] <= bounds[0] && visible[1] <= bounds[1]);
+            assert!(visible[0] + visible[2] >= bounds[2] && visible[1] + visible[3] >= bounds[3]);
+            // The tighter axis keeps exactly the margin.
+            let margin = f64::min(bounds[0] - visible[0], bounds[1] - visible[1]);
+            if view.zoom < MAX_ZOOM {
+                assert_close(margin * view.zoom, FIT_MARGIN);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_clamps_zoom() {
+        let mut view = view();
+        view.fit([0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(view.zoom, MAX_ZOOM);
+        view.fit([0.0, 0.0, 100000.0, 100000.0]);
+        assert_eq!(view.zoom, MIN_ZOOM);
+    }
+}
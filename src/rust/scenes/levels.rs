@@ -0,0 +1,123 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use level_serialization::{Level, load_level, save_level};
+
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Lists the levels of the campaign, one name per line.
+const CAMPAIGN_FILE: &'static str = "campaign.txt";
+
+/// All `.map` files in the directory, sorted by name. Gives none if the directory can't be read.
+pub fn list(dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Failed to list levels in {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut paths = entries.filter_map(Result::ok).map(|i| i.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("map"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Levels named in the directory's campaign manifest, in the order they're played.
+pub fn campaign(dir: &Path) -> Vec<PathBuf> {
+    let mut text = String::new();
+    let path = dir.join(CAMPAIGN_FILE);
+    if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+        println!("Failed to read {}, so no levels will be locked: {}", path.display(), e);
+        return Vec::new();
+    }
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|name| {
+            match path_for(dir, name) {
+                Ok(ref path) if path.exists() => Some(path.clone()),
+                Ok(_) => {
+                    println!("Skipping missing level in {}: {}", CAMPAIGN_FILE, name);
+                    None
+                }
+                Err(e) => {
+                    println!("Skipping level in {}: {}", CAMPAIGN_FILE, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn name(path: &Path) -> String {
+    // unwrap here because all listed paths come from DirEntry, which guarantees a file name.
+    path.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+/// Whether the character can be typed into a level name.
+pub fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+fn path_for(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH || !name.chars().all(is_name_char) {
+        return Err(format!("Invalid level name: {:?}", name));
+    }
+    Ok(dir.join(format!("{}.map", name)))
+}
+
+fn read(path: &Path) -> Result<Level, String> {
+    let mut buf = Vec::new();
+    try!(File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buf))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e)));
+    load_level(&buf).map_err(|e| format!("Failed to load level {}: {:?}", path.display(), e))
+}
+
+/// Loads the level with the given name from the directory.
+pub fn load(dir: &Path, name: &str) -> Result<Level, String> {
+    let path = try!(path_for(dir, name));
+    read(&path)
+}
+
+/// Writes the level to a file which doesn't exist yet.
+fn write_new(path: &Path, level: &Level) -> Result<(), String> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| save_level(level, &mut file))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Creates an empty level named `name` in the directory.
+pub fn create(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = try!(path_for(dir, name));
+    try!(write_new(&path, &Level::empty()));
+    Ok(path)
+}
+
+/// Copies a level to a new name next to it. The level is loaded and saved again rather than the
+/// file being copied, so broken levels aren't duplicated.
+pub fn duplicate(path: &Path, name: &str) -> Result<PathBuf, String> {
+    let level = try!(read(path));
+    let new_path = try!(path_for(path.parent().unwrap_or(Path::new(".")), name));
+    try!(write_new(&new_path, &level));
+    Ok(new_path)
+}
+
+pub fn rename(path: &Path, name: &str) -> Result<PathBuf, String> {
+    let new_path = try!(path_for(path.parent().unwrap_or(Path::new(".")), name));
+    if new_path.exists() {
+        return Err(format!("{} already exists", new_path.display()));
+    }
+    try!(fs::rename(path, &new_path)
+        .map_err(|e| format!("Failed to rename {}: {}", path.display(), e)));
+    Ok(new_path)
+}
+
+pub fn delete(path: &Path) -> Result<(), String> {
+    fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
@@ -1,101 +1,481 @@
 mod play;
+mod controls;
 mod editor;
+mod levels;
+mod stack;
+mod text_input;
 
-use std::ops::Deref;
 use std::f64;
-use std::fs;
-use std::env::current_dir;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
-use piston::input::{Button, Key, PressEvent, RenderEvent};
+use piston::input::{Input, RenderArgs, UpdateArgs};
 use graphics::{self, Context, Transformed};
 use graphics::types::Color;
 use graphics::character::CharacterCache;
 
 use super::{Graphics, GraphicsCache, SettingsChannel, Window};
+use bindings::{Action, ActionTracker, Bindings};
+use mechanics::{PlayerSettings, SettingsUpdate};
+use progress::Progress;
+use settings;
 
-pub type SceneRunFn<'a> = for<'b, 'c, 'd, 'e> Fn(&'b mut Window,
-                                                 &'c mut Graphics,
-                                                 &'d mut GraphicsCache,
-                                                 &'e mut SettingsChannel) + Sync + 'a;
-
-pub static MAIN_MENU: MenuScene<'static> = MenuScene {
-    title: "B/W ADVENTURES",
-    options: &[("PLAY", &play_scene as &SceneRunFn), ("EDIT", &editor_scene as &SceneRunFn)],
-};
-
-fn find_level_dir() -> PathBuf {
-    let cwd = current_dir().unwrap();
-    let mut current_dir: &Path = &cwd;
-    loop {
-        for item in fs::read_dir(&current_dir).unwrap() {
-            let item = item.unwrap();
-            let name = item.file_name();
-
-            let file_type = match item.file_type() {
-                Ok(v) => v,
-                Err(_) => break,
-            };
+use self::stack::{Scene, SceneContext, SceneResult, SceneStack, Transition};
+
+/// Darkens the game behind menus drawn over it.
+const OVERLAY_COLOR: Color = [0.0, 0.0, 0.0, 0.7];
+
+const LEVEL_ACTIONS: [&'static str; 4] = ["EDIT", "RENAME", "DUPLICATE", "DELETE"];
+
+/// Runs the game from the main menu until it's closed.
+pub fn run(window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
+           sc: &mut SettingsChannel) {
+    let mut progress = Progress::load();
+    let mut ctx = SceneContext {
+        graphics: graphics,
+        cache: cache,
+        sc: sc,
+        progress: &mut progress,
+    };
+    SceneStack::new(Box::new(main_menu())).run(window, &mut ctx);
+}
+
+fn main_menu() -> MenuScene {
+    MenuScene::new("B/W ADVENTURES", &["PLAY", "EDIT", "OPTIONS"], |_, index| {
+        let next: Box<Scene> = match index {
+            0 => Box::new(LevelSelect::new()),
+            1 => Box::new(EditorLevels::new()),
+            _ => Box::new(OptionsScene::new()),
+        };
+        Transition::Push(next)
+    })
+}
 
-            if &name[..] == "maps" && file_type.is_dir() {
-                let path = item.path();
-                // to test permissions
-                match fs::symlink_metadata(&path) {
-                    Ok(_) => return path,
-                    Err(_) => break,
+fn find_level_dir() -> Result<PathBuf, String> {
+    ::find_data_dir("maps")
+        .ok_or_else(|| "Reached filesystem root in search for maps dir".to_owned())
+}
+
+/// Lists the campaign levels in order, followed by any others in the maps dir. Campaign levels
+/// are locked until the one before them is completed, and other levels are always unlocked.
+struct LevelSelect {
+    paths: Vec<PathBuf>,
+    campaign_len: usize,
+    menu: Menu,
+}
+
+impl LevelSelect {
+    fn new() -> LevelSelect {
+        let level_dir = match find_level_dir() {
+            Ok(level_dir) => level_dir,
+            Err(e) => {
+                println!("{}", e);
+                return LevelSelect {
+                    paths: Vec::new(),
+                    campaign_len: 0,
+                    menu: Menu::default(),
                 };
             }
+        };
+        let mut paths = levels::campaign(&level_dir);
+        let campaign_len = paths.len();
+        for path in levels::list(&level_dir) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        LevelSelect {
+            paths: paths,
+            campaign_len: campaign_len,
+            menu: Menu::default(),
+        }
+    }
+
+    fn is_unlocked(&self, progress: &Progress, index: usize) -> bool {
+        index == 0 || index >= self.campaign_len ||
+        progress.level(&levels::name(&self.paths[index - 1])).completed
+    }
+
+    /// The campaign levels after the one at `index`, which can be moved on to once it's done.
+    fn later(&self, index: usize) -> Vec<PathBuf> {
+        if index < self.campaign_len {
+            self.paths[index + 1..self.campaign_len].to_vec()
+        } else {
+            Vec::new()
         }
-        current_dir = current_dir.parent().expect("Reached filesystem root in search for maps dir");
     }
 }
 
-fn play_scene(window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-              sc: &mut SettingsChannel) {
-    let level_dir = find_level_dir();
+impl Scene for LevelSelect {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        match self.menu.step(ctx.sc.bindings(), event, self.paths.len()) {
+            MenuStep::Back => Transition::Pop,
+            MenuStep::Done(MenuInput::Chosen(index)) if self.is_unlocked(ctx.progress, index) => {
+                let later = self.later(index);
+                Transition::Push(Box::new(play::PlayScene::new(&self.paths[index], later, ctx)))
+            }
+            _ => Transition::Stay,
+        }
+    }
 
-    let play_options = fs::read_dir(&level_dir).unwrap().filter_map(Result::ok).map(|i| i.path())
-            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("map")).map(|path| {
-        // unwrap here because DirEntry guarantees that there will be a file name.
-        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let labels = self.paths.iter().enumerate().map(|(index, path)| {
+            let name = levels::name(path);
+            let level = ctx.progress.level(&name);
+            if !self.is_unlocked(ctx.progress, index) {
+                format!("{}: LOCKED", name)
+            } else if let Some(time) = level.best_time {
+                format!("{}: BEST {:.2}, DEATHS {}", name, time, level.deaths)
+            } else if level.deaths > 0 {
+                format!("{}: DEATHS {}", name, level.deaths)
+            } else {
+                name
+            }
+        }).collect::<Vec<_>>();
+        render_menu(ctx, args, "CHOOSE LEVEL", &labels, self.menu.selected, false);
+    }
+}
 
-        (name, Box::new(move |window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-                sc: &mut SettingsChannel| {
-            play::PlayScene::new(&path).run(window, graphics, cache, sc);
-        }) as Box<Fn(&mut Window, &mut Graphics, &mut GraphicsCache, &mut SettingsChannel) + Sync>)
-    }).collect::<Vec<_>>();
+/// Lists levels to edit, followed by an entry to create a new one. The list is rebuilt whenever
+/// this is returned to, since levels may have been added, renamed or deleted.
+struct EditorLevels {
+    level_dir: Result<PathBuf, String>,
+    paths: Vec<PathBuf>,
+    menu: Menu,
+}
 
-    let menu = MenuScene {
-        title: "CHOOSE LEVEL",
-        options: &play_options[..],
-    };
+impl EditorLevels {
+    fn new() -> EditorLevels {
+        let level_dir = find_level_dir();
+        if let Err(ref e) = level_dir {
+            println!("{}", e);
+        }
+        EditorLevels {
+            paths: list_levels(&level_dir),
+            level_dir: level_dir,
+            menu: Menu::default(),
+        }
+    }
 
-    menu.run(window, graphics, cache, sc);
+    fn names(&self) -> Vec<String> {
+        let mut names = self.paths.iter().map(|path| levels::name(path)).collect::<Vec<_>>();
+        names.push("NEW LEVEL".to_owned());
+        names
+    }
 }
 
-fn editor_scene(window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-                sc: &mut SettingsChannel) {
-    let level_dir = find_level_dir();
+impl Scene for EditorLevels {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let len = self.paths.len() + 1;
+        match self.menu.step(ctx.sc.bindings(), event, len) {
+            MenuStep::Back => Transition::Pop,
+            MenuStep::Done(MenuInput::Chosen(index)) => {
+                match self.paths.get(index) {
+                    Some(path) => Transition::Push(Box::new(LevelActions::new(path.clone()))),
+                    None => Transition::Push(Box::new(text_input::TextInput::new("NEW LEVEL", ""))),
+                }
+            }
+            _ => Transition::Stay,
+        }
+    }
 
-    let editor_options = fs::read_dir(&level_dir).unwrap().filter_map(Result::ok).map(|i| i.path())
-            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("map")).map(|path| {
-        // unwrap here because DirEntry guarantees that there will be a file name.
-        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let names = self.names();
+        render_menu(ctx, args, "CHOOSE LEVEL", &names, self.menu.selected, false);
+    }
 
-        (name, Box::new(move |window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-                sc: &mut SettingsChannel| {
-            editor::EditorScene::new(&path).run(window, graphics, cache, sc);
-        }) as Box<Fn(&mut Window, &mut Graphics, &mut GraphicsCache, &mut SettingsChannel) + Sync>)
-    }).collect::<Vec<_>>();
+    fn resume(&mut self, ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        let mut next = Transition::Stay;
+        // Only the new level prompt gives back text.
+        if let Some(SceneResult::Text(name)) = result {
+            let created = self.level_dir.clone().and_then(|dir| levels::create(&dir, &name));
+            match created {
+                Ok(path) => next = Transition::Push(Box::new(editor::EditorScene::new(&path, ctx))),
+                Err(e) => println!("{}", e),
+            }
+        }
+        self.paths = list_levels(&self.level_dir);
+        next
+    }
+}
 
-    let menu = MenuScene {
-        title: "CHOOSE LEVEL",
-        options: &editor_options[..],
-    };
+/// Lists the levels in the maps dir, or none if it couldn't be found.
+fn list_levels(level_dir: &Result<PathBuf, String>) -> Vec<PathBuf> {
+    match *level_dir {
+        Ok(ref dir) => levels::list(dir),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Menu of things to do with a single level. Closes once the level has been renamed, duplicated
+/// or deleted so the level list can be refreshed.
+struct LevelActions {
+    path: PathBuf,
+    name: String,
+    menu: Menu,
+    /// The action waiting on a prompt or confirmation.
+    pending: Option<&'static str>,
+}
+
+impl LevelActions {
+    fn new(path: PathBuf) -> LevelActions {
+        LevelActions {
+            name: levels::name(&path),
+            path: path,
+            menu: Menu::default(),
+            pending: None,
+        }
+    }
+}
+
+impl Scene for LevelActions {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let index = match self.menu.step(ctx.sc.bindings(), event, LEVEL_ACTIONS.len()) {
+            MenuStep::Back => return Transition::Pop,
+            MenuStep::Done(MenuInput::Chosen(index)) => index,
+            _ => return Transition::Stay,
+        };
+        let action = LEVEL_ACTIONS[index];
+        self.pending = Some(action);
+        let next: Box<Scene> = match action {
+            "EDIT" => Box::new(editor::EditorScene::new(&self.path, ctx)),
+            "RENAME" => Box::new(text_input::TextInput::new("RENAME LEVEL", &self.name)),
+            "DUPLICATE" => {
+                let copy_name = format!("{}-copy", self.name);
+                Box::new(text_input::TextInput::new("DUPLICATE LEVEL", &copy_name))
+            }
+            _ => {
+                let question = format!("DELETE {}?", self.name);
+                Box::new(MenuScene::choice(&question, &["NO", "YES"]))
+            }
+        };
+        Transition::Push(next)
+    }
 
-    menu.run(window, graphics, cache, sc);
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let title = format!("LEVEL: {}", self.name);
+        render_menu(ctx, args, &title, &LEVEL_ACTIONS, self.menu.selected, false);
+    }
+
+    fn resume(&mut self, _ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        let result = match (self.pending.take(), result) {
+            (Some("RENAME"), Some(SceneResult::Text(new_name))) => {
+                levels::rename(&self.path, &new_name).map(|_| ())
+            }
+            (Some("DUPLICATE"), Some(SceneResult::Text(new_name))) => {
+                levels::duplicate(&self.path, &new_name).map(|_| ())
+            }
+            (Some("DELETE"), Some(SceneResult::Chosen(1))) => levels::delete(&self.path),
+            _ => return Transition::Stay,
+        };
+        match result {
+            Ok(()) => Transition::Pop,
+            Err(e) => {
+                println!("{}", e);
+                Transition::Stay
+            }
+        }
+    }
 }
 
+/// Physics settings, adjusted with left and right the same way as the settings window's sliders.
+struct OptionsScene {
+    menu: Menu,
+}
+
+impl OptionsScene {
+    fn new() -> OptionsScene {
+        OptionsScene { menu: Menu::default() }
+    }
+}
+
+/// The settings which can be adjusted from the options menu, in order.
+fn adjustable_settings(sc: &SettingsChannel) -> Vec<SettingsUpdate> {
+    sc.current().updates().into_iter()
+        .filter(|update| update.range().is_some())
+        .collect()
+}
+
+impl Scene for OptionsScene {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let updates = adjustable_settings(ctx.sc);
+        match self.menu.step(ctx.sc.bindings(), event, updates.len() + 3) {
+            MenuStep::Stay => Transition::Stay,
+            MenuStep::Back => Transition::Pop,
+            MenuStep::Done(MenuInput::Adjusted(index, direction)) => {
+                if let Some(&update) = updates.get(index) {
+                    let (min, max, step) = update.range().unwrap();
+                    // Rounded to the step so repeated adjustments don't drift.
+                    let value = ((update.value() + step * direction as f64) / step).round() * step;
+                    ctx.sc.send(update.with_value(f64::max(min, f64::min(max, value))));
+                }
+                Transition::Stay
+            }
+            MenuStep::Done(MenuInput::Chosen(index)) => {
+                if index == updates.len() {
+                    for update in PlayerSettings::default().updates() {
+                        ctx.sc.send(update);
+                    }
+                } else if index == updates.len() + 1 {
+                    match settings::save(ctx.sc.current()) {
+                        Ok(path) => println!("Saved settings to {}", path.display()),
+                        Err(e) => println!("Failed to save settings: {}", e),
+                    }
+                } else if index == updates.len() + 2 {
+                    return Transition::Push(Box::new(controls::ControlsScene::new()));
+                }
+                Transition::Stay
+            }
+        }
+    }
+
+    /// Follows changes from the settings window while open.
+    fn update(&mut self, ctx: &mut SceneContext, _args: &UpdateArgs) -> Transition {
+        ctx.sc.poll();
+        Transition::Stay
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let mut labels = adjustable_settings(ctx.sc).iter().map(|update| {
+            let (_, _, step) = update.range().unwrap();
+            let decimals = f64::max(0.0, -step.log10().round()) as usize;
+            format!("{}: {:.*}", update.name().replace('_', " ").to_uppercase(), decimals,
+                    update.value())
+        }).collect::<Vec<_>>();
+        labels.push("RESET TO DEFAULTS".to_owned());
+        labels.push("SAVE".to_owned());
+        labels.push("CONTROLS".to_owned());
+
+        render_menu(ctx, args, "OPTIONS", &labels, self.menu.selected, false);
+    }
+}
+
+/// A menu with a fixed list of options, which calls `on_choose` with the index of each option
+/// chosen.
+pub struct MenuScene {
+    title: String,
+    options: Vec<String>,
+    menu: Menu,
+    overlay: bool,
+    on_choose: Box<FnMut(&mut SceneContext, usize) -> Transition>,
+}
+
+impl MenuScene {
+    pub fn new<T, F>(title: &str, options: &[T], on_choose: F) -> MenuScene
+        where T: AsRef<str>,
+              F: FnMut(&mut SceneContext, usize) -> Transition + 'static
+    {
+        MenuScene {
+            title: title.to_owned(),
+            options: options.iter().map(|option| option.as_ref().to_owned()).collect(),
+            menu: Menu::default(),
+            overlay: false,
+            on_choose: Box::new(on_choose),
+        }
+    }
+
+    /// A menu which closes as soon as an option is chosen, giving its index to the scene below.
+    pub fn choice<T: AsRef<str>>(title: &str, options: &[T]) -> MenuScene {
+        MenuScene::new(title, options, |_, index| Transition::PopWith(SceneResult::Chosen(index)))
+    }
+
+    /// Draws the menu over the scene below it. Pausing closes it, as well as going back.
+    pub fn overlay(mut self) -> MenuScene {
+        self.overlay = true;
+        self
+    }
+}
+
+impl Scene for MenuScene {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let actions = self.menu.tracker.pressed(ctx.sc.bindings(), event);
+        if self.overlay && actions.contains(&Action::Pause) {
+            return Transition::Pop;
+        }
+        match self.menu.select(&actions, self.options.len()) {
+            MenuStep::Back => Transition::Pop,
+            MenuStep::Done(MenuInput::Chosen(index)) => {
+                println!("Selected: {}", index);
+                (self.on_choose)(ctx, index)
+            }
+            _ => Transition::Stay,
+        }
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        render_menu(ctx, args, &self.title, &self.options, self.menu.selected, self.overlay);
+    }
+
+    fn is_overlay(&self) -> bool {
+        self.overlay
+    }
+}
+
+/// The selected option and held buttons of a menu.
+#[derive(Default)]
+struct Menu {
+    selected: usize,
+    tracker: ActionTracker,
+}
+
+impl Menu {
+    /// Handles an event for a menu with `len` options, giving what it did.
+    fn step(&mut self, bindings: &Bindings, event: &Input, len: usize) -> MenuStep {
+        let actions = self.tracker.pressed(bindings, event);
+        self.select(&actions, len)
+    }
+
+    /// Like `step`, for actions which have already been taken from an event.
+    fn select(&mut self, actions: &[Action], len: usize) -> MenuStep {
+        // The options may have changed since the last event.
+        if self.selected >= len {
+            self.selected = 0;
+        }
+        menu_step(actions, &mut self.selected, len)
+    }
+}
+
+/// What was done with a menu.
+enum MenuInput {
+    Chosen(usize),
+    /// Left (-1) or right (1) was pressed on an option.
+    Adjusted(usize, i32),
+}
+
+/// What pressing some actions did to a menu.
+enum MenuStep {
+    /// Nothing, or just moved the selection.
+    Stay,
+    Back,
+    Done(MenuInput),
+}
+
+/// Moves `selected` for up and down, and gives what else the actions did to the menu.
+fn menu_step(actions: &[Action], selected: &mut usize, len: usize) -> MenuStep {
+    if actions.contains(&Action::Back) {
+        return MenuStep::Back;
+    }
+    if len == 0 {
+        return MenuStep::Stay;
+    }
+    if actions.contains(&Action::Confirm) {
+        return MenuStep::Done(MenuInput::Chosen(*selected));
+    } else if actions.contains(&Action::MoveLeft) {
+        return MenuStep::Done(MenuInput::Adjusted(*selected, -1));
+    } else if actions.contains(&Action::MoveRight) {
+        return MenuStep::Done(MenuInput::Adjusted(*selected, 1));
+    } else if actions.contains(&Action::MenuUp) {
+        if *selected == 0 {
+            // selected is usize
+            *selected = len - 1;
+        } else {
+            *selected -= 1;
+        }
+    } else if actions.contains(&Action::MenuDown) {
+        *selected += 1;
+        *selected %= len;
+    }
+    MenuStep::Stay
+}
 
 fn draw_text<T: AsRef<str>>(position: [f64; 4], text: T, text_size: u32, color: Color,
                             cache: &mut GraphicsCache, context: &Context, graphics: &mut Graphics) {
@@ -127,103 +507,65 @@ fn draw_text<T: AsRef<str>>(position: [f64; 4], text: T, text_size: u32, color:
     }
 }
 
-pub struct MenuScene<'a, TiT = &'a str, OpT = &'a str, FnT = &'a SceneRunFn<'a>>
-    where TiT: AsRef<str> + 'a,
-          OpT: AsRef<str> + 'a,
-          FnT: Deref<Target = SceneRunFn<'a>> + 'a
-{
-    title: TiT,
-    options: &'a [(OpT, FnT)],
-}
+/// Draws a menu. As an overlay, it darkens the scenes drawn below it instead of clearing the
+/// screen.
+fn render_menu<T: AsRef<str>>(ctx: &mut SceneContext, args: &RenderArgs, title: &str,
+                              options: &[T], selected: usize, overlay: bool) {
+    let screen_width = args.width as f64;
+    let screen_height = args.height as f64;
 
-impl<'a, TiT, OpT, FnT> MenuScene<'a, TiT, OpT, FnT>
-    where TiT: AsRef<str> + 'a,
-          OpT: AsRef<str> + 'a,
-          FnT: Deref<Target = SceneRunFn<'a>> + 'a
-{
-    pub fn run(&self, window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-               sc: &mut SettingsChannel) {
-        let mut selected = 0usize;
+    let viewport = graphics::Viewport {
+        rect: [0, 0, args.width as i32, args.height as i32],
+        draw_size: [1; 2],
+        window_size: [1; 2],
+    };
 
-        while let Some(event) = window.next() {
-            if let Some(Button::Keyboard(Key::Escape)) = event.press_args() {
-                break;
-            }
+    let cache = &mut *ctx.cache;
+    ctx.graphics.draw(viewport, |context, graphics| {
+        if overlay {
+            graphics::Rectangle::new(OVERLAY_COLOR).draw(
+                [0.0, 0.0, screen_width, screen_height],
+                &context.draw_state,
+                context.transform,
+                graphics,
+            );
+        } else {
+            graphics::clear(graphics::color::BLACK, graphics);
+        }
+        let width = f64::min(screen_width * 0.8, 400.0).floor();
+        let height = f64::min(screen_height * 0.8 / ((options.len() + 2) as f64 * 1.2), 20.0)
+            .floor();
+        let x_pos = ((screen_width - width) / 2.0).floor();
 
-            event.render(|event| {
-                let screen_width = event.width as f64;
-                let screen_height = event.height as f64;
+        draw_text(
+            [x_pos, screen_height * 0.2, width, height * 1.5],
+            title,
+            (height * 1.5) as u32,
+            graphics::color::WHITE,
+            cache, &context, graphics,
+        );
 
-                let viewport = graphics::Viewport {
-                    rect: [0, 0, event.width as i32, event.height as i32],
-                    draw_size: [1; 2],
-                    window_size: [1; 2],
-                };
+        for (index, text) in options.iter().enumerate() {
+            let y_pos = (screen_height * 0.2 + (index + 2) as f64 * height * 1.2).floor();
+            let color = if index == selected {
+                graphics::color::WHITE
+            } else {
+                graphics::color::grey(0.2)
+            };
+            graphics::Rectangle::new(color).draw(
+                [x_pos, y_pos, width, height],
+                &context.draw_state,
+                context.transform,
+                graphics,
+            );
 
-                graphics.draw(viewport, |context, graphics| {
-                    graphics::clear(graphics::color::BLACK, graphics);
-                    let width = f64::min(screen_width * 0.8, 400.0).floor();
-                    let height = f64::min(screen_height * 0.8 /
-                                          ((self.options.len() + 2) as f64 * 1.2),
-                                          20.0)
-                        .floor();
-                    let x_pos = ((screen_width - width) / 2.0).floor();
-
-                    draw_text(
-                        [x_pos, screen_height * 0.2, width, height * 1.5],
-                        &self.title,
-                        (height * 1.5) as u32,
-                        graphics::color::WHITE,
-                        cache, &context, graphics,
-                    );
-
-                    for (index, &(ref text, _)) in self.options.iter().enumerate() {
-                        let y_pos = (screen_height * 0.2 + (index + 2) as f64 * height * 1.2)
-                            .floor();
-                        let color = if index == selected {
-                            graphics::color::WHITE
-                        } else {
-                            graphics::color::grey(0.2)
-                        };
-                        graphics::Rectangle::new(color).draw(
-                            [x_pos, y_pos, width, height],
-                            &context.draw_state,
-                            context.transform,
-                            graphics,
-                        );
-
-                        draw_text(
-                            [x_pos, y_pos, width, height],
-                            text,
-                            (height * 0.8) as u32,
-                            graphics::color::BLACK,
-                            cache, &context, graphics,
-                        );
-                    }
-                })
-            });
-
-            event.press(|b| {
-                match b {
-                    Button::Keyboard(Key::Up) => {
-                        if selected == 0 {
-                            // selected is usize
-                            selected = self.options.len() - 1;
-                        } else {
-                            selected -= 1;
-                        }
-                    }
-                    Button::Keyboard(Key::Down) => {
-                        selected += 1;
-                        selected %= self.options.len();
-                    }
-                    Button::Keyboard(Key::Return) => {
-                        println!("Selected: {}", selected);
-                        (self.options[selected].1)(window, graphics, cache, sc);
-                    }
-                    _ => (),
-                }
-            });
+            draw_text(
+                [x_pos, y_pos, width, height],
+                text,
+                (height * 0.8) as u32,
+                graphics::color::BLACK,
+                cache, &context, graphics,
+            );
         }
-    }
+    })
 }
@@ -1,68 +1,265 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::Read;
 
-use piston::input::{Button, Key, PressEvent, RenderArgs, RenderEvent, Input};
-use graphics::{self, Transformed};
+use piston::input::{Input, RenderArgs, UpdateArgs};
+use graphics::{self, ImageSize, Transformed};
 
-use super::super::{Graphics, GraphicsCache, SettingsChannel, Window};
+use super::super::{Hitboxes, SettingsChannel};
+use super::{MenuScene, OptionsScene, find_level_dir, levels};
+use super::stack::{Scene, SceneContext, SceneResult, Transition};
+use bindings::{Action, ActionTracker};
+use camera::Camera;
+use collisions::HasBounds;
+use control::Command;
+use entities::{self, Entity};
 use level_serialization::{Level, load_level};
 use map::Map;
-use player::{PLAYER_IMAGE_X_OFFSET, PLAYER_IMAGE_Y_OFFSET, Player};
+use player::Player;
+use progress::Progress;
 
+/// Seconds between sending the player's state to the settings window.
+const TELEMETRY_INTERVAL: f64 = 0.25;
+
+const PAUSE_OPTIONS: [&'static str; 4] = ["RESUME", "RESTART", "OPTIONS", "QUIT TO MENU"];
+
+/// Plays a level until quit from the pause menu, recording deaths and completion times. Switches
+/// to another level if the control socket loads one.
 pub struct PlayScene {
-    map: Level,
+    level: Level,
+    /// The level's file name, which its progress is saved under. None for levels loaded by the
+    /// control socket, which skip the campaign locks and so don't count towards progress.
+    name: Option<String>,
+    /// Campaign levels after this one, in order.
+    later: Vec<PathBuf>,
+    session: PlayData,
+    tracker: ActionTracker,
+    /// Seconds played since the level was started or restarted, not counting time paused.
+    time: f64,
+    /// Deaths already added to the saved progress.
+    deaths_recorded: u32,
+    /// Set once the goal is reached, until the level is restarted.
+    completed: bool,
 }
 
 impl PlayScene {
-    pub fn new<T: AsRef<Path> + Copy>(level_file: T) -> PlayScene {
+    pub fn new<T: AsRef<Path> + Copy>(level_file: T, later: Vec<PathBuf>, ctx: &mut SceneContext)
+                                      -> PlayScene {
         let mut buf = Vec::new();
         {
             let mut file = File::open(level_file).unwrap();
             file.read_to_end(&mut buf).unwrap();
         }
+        let level = load_level(&buf)
+            .expect(&format!("Failed to load level: {}", level_file.as_ref().display()));
+        PlayScene::with_level(level, Some(levels::name(level_file.as_ref())), later, ctx)
+    }
+
+    pub fn with_level(level: Level, name: Option<String>, later: Vec<PathBuf>,
+                      ctx: &mut SceneContext)
+                      -> PlayScene {
+        // Anything sent while no level was playing has already timed out.
+        ctx.sc.control_requests();
         PlayScene {
-            map: load_level(&buf)
-                .expect(&format!("Failed to load level: {}", level_file.as_ref().display())),
+            session: PlayData::new(&level, ctx.cache.hitboxes(), ctx.sc),
+            level: level,
+            name: name,
+            later: later,
+            tracker: ActionTracker::default(),
+            time: 0.0,
+            deaths_recorded: 0,
+            completed: false,
+        }
+    }
+
+    fn restart(&mut self, ctx: &mut SceneContext) {
+        let level = &self.level;
+        self.session.restart(level, level.initial_x, level.initial_y, ctx.sc);
+        self.time = 0.0;
+        self.completed = false;
+    }
+
+    /// Adds deaths since the last call to the saved progress.
+    fn record_deaths(&mut self, ctx: &mut SceneContext) {
+        let deaths = self.session.player.state.deaths;
+        if deaths > self.deaths_recorded {
+            if let Some(ref name) = self.name {
+                ctx.progress.add_deaths(name, deaths - self.deaths_recorded);
+                save_progress(ctx.progress);
+            }
+            self.deaths_recorded = deaths;
+        }
+    }
+
+    fn complete_options(&self) -> Vec<&'static str> {
+        let mut options = vec!["REPLAY", "QUIT TO MENU"];
+        if !self.later.is_empty() {
+            options.insert(0, "NEXT LEVEL");
+        }
+        options
+    }
+}
+
+fn pause_menu() -> MenuScene {
+    MenuScene::new("PAUSED", &PAUSE_OPTIONS, |_, index| {
+        match PAUSE_OPTIONS[index] {
+            // Opened over the pause menu, which is returned to afterwards.
+            "OPTIONS" => Transition::Push(Box::new(OptionsScene::new())),
+            _ => Transition::PopWith(SceneResult::Chosen(index)),
+        }
+    }).overlay()
+}
+
+fn save_progress(progress: &Progress) {
+    if let Err(e) = progress.save() {
+        println!("Failed to save progress: {}", e);
+    }
+}
+
+impl Scene for PlayScene {
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition {
+        let actions = self.tracker.pressed(ctx.sc.bindings(), event);
+        if actions.contains(&Action::Back) || actions.contains(&Action::Pause) {
+            return Transition::Push(Box::new(pause_menu()));
+        } else if actions.contains(&Action::Restart) {
+            self.restart(ctx);
+        }
+        self.session.input(event);
+        Transition::Stay
+    }
+
+    fn update(&mut self, ctx: &mut SceneContext, args: &UpdateArgs) -> Transition {
+        self.session.update(args, ctx.sc);
+        self.time += args.dt;
+        self.record_deaths(ctx);
+
+        if self.session.player.state.reached_goal && !self.completed {
+            self.completed = true;
+            let best = match self.name {
+                Some(ref name) => {
+                    let best = ctx.progress.complete(name, self.time);
+                    save_progress(ctx.progress);
+                    best
+                }
+                None => false,
+            };
+            let title = format!("{}: {:.2}", if best { "NEW BEST" } else { "COMPLETE" }, self.time);
+            let menu = MenuScene::choice(&title, &self.complete_options()).overlay();
+            return Transition::Push(Box::new(menu));
+        }
+
+        for request in ctx.sc.control_requests() {
+            match request.command {
+                Command::GetState => request.reply(Ok(self.session.state_line())),
+                Command::LoadLevel(ref name) => {
+                    match find_level_dir().and_then(|dir| levels::load(&dir, name)) {
+                        Ok(next) => {
+                            request.reply(Ok(String::new()));
+                            let next = PlayScene::with_level(next, None, Vec::new(), ctx);
+                            return Transition::Replace(Box::new(next));
+                        }
+                        Err(e) => request.reply(Err(e)),
+                    }
+                }
+            }
         }
+        Transition::Stay
     }
 
-    pub fn run(&self, window: &mut Window, graphics: &mut Graphics, cache: &mut GraphicsCache,
-               sc: &mut SettingsChannel) {
-        let mut session = PlayData::new(&self.map, graphics, cache, sc);
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        self.session.render(ctx, args);
+    }
 
-        while let Some(event) = window.next() {
-            if let Some(Button::Keyboard(Key::Escape)) = event.press_args() {
-                break;
+    fn resume(&mut self, ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        // Keys and sticks let go of while paused would otherwise stay held.
+        self.tracker = ActionTracker::default();
+        self.session.player.state.clear_input();
+        // The options menu may have been opened from the pause menu.
+        self.session.player.state.reload_settings(&self.level.physics, ctx.sc);
+        let options = if self.completed {
+            self.complete_options()
+        } else {
+            PAUSE_OPTIONS.to_vec()
+        };
+        let chosen = match result {
+            Some(SceneResult::Chosen(index)) => options[index],
+            // Going back from the completion menu leaves the level, since it's over.
+            _ if self.completed => "QUIT TO MENU",
+            _ => "RESUME",
+        };
+        match chosen {
+            "RESTART" | "REPLAY" => self.restart(ctx),
+            "NEXT LEVEL" => {
+                let next = PlayScene::new(&self.later[0], self.later[1..].to_vec(), ctx);
+                return Transition::Replace(Box::new(next));
             }
-            session.process(&event);
+            "QUIT TO MENU" => return Transition::Pop,
+            _ => (),
         }
+        Transition::Stay
     }
 }
 
-pub struct PlayData<'a> {
-    pub graphics: &'a mut Graphics,
-    pub cache: &'a mut GraphicsCache,
+pub struct PlayData {
     pub map: Map,
-    pub player: Player<'a>,
+    pub player: Player,
+    pub camera: Camera,
+    pub entities: Vec<Box<Entity>>,
+    hitboxes: Hitboxes,
+    /// Frames drawn, and seconds passed, since telemetry was last sent.
+    frames: u32,
+    report_time: f64,
 }
 
-impl<'a> PlayData<'a> {
-    pub fn new<'b>(level: &Level, graphics: &'b mut Graphics, cache: &'b mut GraphicsCache,
-                   sc: &'b mut SettingsChannel)
-                   -> PlayData<'b> {
+impl PlayData {
+    pub fn new(level: &Level, hitboxes: Hitboxes, sc: &SettingsChannel) -> PlayData {
         let map = Map::from(level);
+        let mut player = Player::new(map.initial_x(), map.initial_y(), sc);
+        player.state.set_collision_box(hitboxes.player);
+        player.state.override_settings(&level.physics, sc);
+        let target = player.camera_target();
         PlayData {
-            graphics: graphics,
-            cache: cache,
-            player: Player::new(map.initial_x(), map.initial_y(), sc),
+            player: player,
+            camera: Camera::new(target.x, target.y),
+            entities: spawn_entities(level, hitboxes),
+            hitboxes: hitboxes,
             map: map,
+            frames: 0,
+            report_time: 0.0,
         }
     }
 
-    fn render(&mut self, event: &RenderArgs) {
+    /// Puts the player back at (x, y) and respawns all of the level's entities.
+    pub fn restart(&mut self, level: &Level, x: f64, y: f64, sc: &SettingsChannel) {
+        self.player.state.reset_at(x, y);
+        self.player.state.override_settings(&level.physics, sc);
+        self.player.animation_time = 0.0;
+        self.entities = spawn_entities(level, self.hitboxes);
+        self.camera.reset();
+    }
+
+    /// The player's state as `name=value` pairs, for the control socket.
+    pub fn state_line(&self) -> String {
+        let state = &self.player.state;
+        format!("x={:.2} y={:.2} velocity_x={:.2} velocity_y={:.2} grounded={} \
+                 on_left_wall={} on_right_wall={} health={} deaths={}",
+                state.absolute_x, state.absolute_y, state.velocity_x(), state.velocity_y(),
+                state.grounded, state.on_left_wall, state.on_right_wall, state.health(),
+                state.deaths)
+    }
+
+    pub fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        self.camera.resize(args.width as f64, args.height as f64);
+        let (scroll_x, scroll_y) = self.camera.scroll();
+        self.render_view(ctx, args, scroll_x, scroll_y, 1.0, true);
+    }
+
+    /// Draws the level centered on the given world coordinates, scaled by `zoom`.
+    pub fn render_view(&mut self, ctx: &mut SceneContext, event: &RenderArgs, center_x: f64,
+                       center_y: f64, zoom: f64, show_player: bool) {
         let screen_width = event.width as f64;
         let screen_height = event.height as f64;
+        self.frames += 1;
 
         // TODO: see if the [1; 2] instead of [0; 2] wants to be included in any example projects
         let viewport = graphics::Viewport {
@@ -71,42 +268,104 @@ impl<'a> PlayData<'a> {
             window_size: [1; 2],
         };
 
-        let (scroll_x, scroll_y) = self.player.calculate_scroll(screen_width, screen_height);
-        let cache = &self.cache;
+        let cache = &*ctx.cache;
         let player = &self.player;
         let map = &self.map;
+        let entities = &self.entities;
 
-        self.graphics.draw(viewport, |context, graphics| {
-            let context = context.trans(-scroll_x, scroll_y);
+        ctx.graphics.draw(viewport, |screen_context, graphics| {
+            // World origin, with y going down.
+            let world_context = screen_context.trans(screen_width / 2.0, screen_height / 2.0)
+                .zoom(zoom)
+                .trans(-center_x, center_y);
             graphics::clear(graphics::color::BLACK, graphics);
             graphics::Rectangle::new(graphics::color::WHITE).draw(
                 map.boundaries(),
-                &context.draw_state,
-                context.trans(screen_width / 2.0, screen_height / 2.0).flip_v().transform,
-                graphics,
-            );
-            graphics::image(
-                player.get_current_image(&cache.player),
-                context.trans(
-                    screen_width / 2.0 + player.state.absolute_x.ceil() + PLAYER_IMAGE_X_OFFSET as f64,
-                    screen_height / 2.0 - cache.player.get_height() as f64
-                        - player.state.absolute_y.ceil() - PLAYER_IMAGE_Y_OFFSET as f64
-                ).transform,
+                &world_context.draw_state,
+                world_context.flip_v().transform,
                 graphics,
             );
+            for entity in entities {
+                entity.render(cache, &world_context, graphics);
+            }
+            if show_player && player.is_visible() {
+                let frame = player.get_current_frame(&cache.player);
+                graphics::image(
+                    frame.texture,
+                    world_context.trans(
+                        player.state.absolute_x.ceil() + frame.x_offset,
+                        -(frame.texture.get_height() as f64 + player.state.absolute_y.ceil()
+                            + frame.y_offset)
+                    ).transform,
+                    graphics,
+                );
+            }
             for block in map.blocks() {
                 graphics::Rectangle::new(graphics::color::BLACK).draw(
                     block,
-                    &context.draw_state,
-                    context.trans(screen_width / 2.0, screen_height / 2.0).flip_v().transform,
+                    &world_context.draw_state,
+                    world_context.flip_v().transform,
                     graphics,
                 );
             }
+            if show_player {
+                for index in 0..player.state.health() {
+                    graphics::Rectangle::new(graphics::color::grey(0.5)).draw(
+                        [10.0 + index as f64 * 14.0, 10.0, 10.0, 10.0],
+                        &screen_context.draw_state,
+                        screen_context.transform,
+                        graphics,
+                    );
+                }
+            }
         })
     }
 
-    pub fn process(&mut self, event: &Input) {
-        event.render(|event| self.render(event));
-        self.player.event(&event, &self.map);
+    pub fn input(&mut self, event: &Input) {
+        self.player.input(event);
     }
+
+    pub fn update(&mut self, args: &UpdateArgs, sc: &mut SettingsChannel) {
+        let was_dead = self.player.state.is_dead();
+        self.player.update(args, &self.map, sc);
+        if was_dead && !self.player.state.is_dead() {
+            self.camera.reset();
+        }
+        for entity in &mut self.entities {
+            entity.update(args, &self.map);
+        }
+        {
+            let player = &mut self.player.state;
+            let player_bounds = [player.min_x(), player.min_y(),
+                                 player.len_x(), player.len_y()];
+            for entity in &mut self.entities {
+                if entities::overlaps(entity.bounds(), player_bounds) {
+                    entity.on_player_contact(player);
+                }
+            }
+        }
+        self.entities.retain(|entity| !entity.is_removed());
+
+        let target = self.player.camera_target();
+        self.camera.update(args.dt, &target, self.map.boundaries(), self.map.camera_zones());
+
+        self.report_time += args.dt;
+        if self.report_time >= TELEMETRY_INTERVAL {
+            self.player.state.report(self.frames as f64 / self.report_time, sc);
+            self.frames = 0;
+            self.report_time = 0.0;
+        }
+    }
+}
+
+fn spawn_entities(level: &Level, hitboxes: Hitboxes) -> Vec<Box<Entity>> {
+    level.entities.iter().filter_map(|def| {
+        match entities::spawn(def, hitboxes) {
+            Ok(entity) => Some(entity),
+            Err(e) => {
+                println!("Skipping entity.{}: {}", def.kind, e);
+                None
+            }
+        }
+    }).collect()
 }
@@ -0,0 +1,124 @@
+use piston::input::{Input, Key, RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
+
+use ::{Graphics, GraphicsCache, SettingsChannel, Window};
+use progress::Progress;
+
+/// Everything scenes share, lent to whichever scene is handling an event.
+pub struct SceneContext<'a> {
+    pub graphics: &'a mut Graphics,
+    pub cache: &'a mut GraphicsCache,
+    pub sc: &'a mut SettingsChannel,
+    pub progress: &'a mut Progress,
+}
+
+/// What a scene gives back to the scene below it when it's popped.
+pub enum SceneResult {
+    /// The index of a menu option.
+    Chosen(usize),
+    Text(String),
+    Key(Key),
+}
+
+/// What the stack should do after a scene has handled something.
+pub enum Transition {
+    Stay,
+    /// Starts a scene on top of this one, which is resumed once it's popped.
+    Push(Box<Scene>),
+    Pop,
+    PopWith(SceneResult),
+    /// Pops this scene and pushes another in its place, without resuming the one below.
+    Replace(Box<Scene>),
+}
+
+pub trait Scene {
+    /// Handles any input other than rendering and updates. Only the top scene gets these.
+    fn event(&mut self, ctx: &mut SceneContext, event: &Input) -> Transition;
+
+    /// Advances time. Only the top scene is updated, so everything under it is frozen.
+    fn update(&mut self, _ctx: &mut SceneContext, _args: &UpdateArgs) -> Transition {
+        Transition::Stay
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs);
+
+    /// Called once the scene above this one is popped, with what it gave back.
+    fn resume(&mut self, _ctx: &mut SceneContext, _result: Option<SceneResult>) -> Transition {
+        Transition::Stay
+    }
+
+    /// Overlays are drawn over the scene below them, instead of on a cleared screen.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<Scene>>,
+}
+
+impl SceneStack {
+    pub fn new(first: Box<Scene>) -> SceneStack {
+        SceneStack { scenes: vec![first] }
+    }
+
+    /// Runs until every scene has been popped, or the window is closed.
+    pub fn run(&mut self, window: &mut Window, ctx: &mut SceneContext) {
+        while let Some(event) = window.next() {
+            let transition = if let Some(args) = event.render_args() {
+                self.render(ctx, &args);
+                Transition::Stay
+            } else {
+                let scene = match self.scenes.last_mut() {
+                    Some(scene) => scene,
+                    None => break,
+                };
+                match event.update_args() {
+                    Some(args) => scene.update(ctx, &args),
+                    None => scene.event(ctx, &event),
+                }
+            };
+            self.apply(ctx, transition);
+            if self.scenes.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Draws the top scene, along with every scene under it down to the first one which isn't
+    /// an overlay.
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let bottom = self.scenes.iter().rposition(|scene| !scene.is_overlay()).unwrap_or(0);
+        for scene in &mut self.scenes[bottom..] {
+            scene.render(ctx, args);
+        }
+    }
+
+    fn apply(&mut self, ctx: &mut SceneContext, transition: Transition) {
+        let mut transition = transition;
+        loop {
+            // Resuming a scene can give another transition, such as popping straight away.
+            transition = match transition {
+                Transition::Stay => return,
+                Transition::Push(scene) => {
+                    self.scenes.push(scene);
+                    return;
+                }
+                Transition::Replace(scene) => {
+                    self.scenes.pop();
+                    self.scenes.push(scene);
+                    return;
+                }
+                Transition::Pop => self.pop(ctx, None),
+                Transition::PopWith(result) => self.pop(ctx, Some(result)),
+            };
+        }
+    }
+
+    fn pop(&mut self, ctx: &mut SceneContext, result: Option<SceneResult>) -> Transition {
+        self.scenes.pop();
+        match self.scenes.last_mut() {
+            Some(scene) => scene.resume(ctx, result),
+            None => Transition::Stay,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use piston::input::{Button, Input, Key, PressEvent, RenderArgs, TextEvent};
+use graphics;
+
+use super::{draw_text, levels};
+use super::stack::{Scene, SceneContext, SceneResult, Transition};
+
+/// Asks for a level name with `title` above the text box, giving it back once enter is pressed.
+/// Escape closes it without giving anything.
+pub struct TextInput {
+    title: String,
+    text: String,
+}
+
+impl TextInput {
+    pub fn new(title: &str, initial: &str) -> TextInput {
+        TextInput {
+            title: title.to_owned(),
+            text: initial.to_owned(),
+        }
+    }
+}
+
+impl Scene for TextInput {
+    fn event(&mut self, _ctx: &mut SceneContext, event: &Input) -> Transition {
+        match event.press_args() {
+            Some(Button::Keyboard(Key::Escape)) => return Transition::Pop,
+            Some(Button::Keyboard(Key::Return)) if !self.text.is_empty() => {
+                return Transition::PopWith(SceneResult::Text(self.text.clone()));
+            }
+            Some(Button::Keyboard(Key::Backspace)) => {
+                self.text.pop();
+            }
+            _ => (),
+        }
+
+        let text = &mut self.text;
+        event.text(|typed| {
+            for c in typed.chars().filter(|&c| levels::is_name_char(c)) {
+                if text.len() < levels::MAX_NAME_LENGTH {
+                    text.push(c);
+                }
+            }
+        });
+        Transition::Stay
+    }
+
+    fn render(&mut self, ctx: &mut SceneContext, args: &RenderArgs) {
+        let screen_width = args.width as f64;
+        let screen_height = args.height as f64;
+
+        let viewport = graphics::Viewport {
+            rect: [0, 0, args.width as i32, args.height as i32],
+            draw_size: [1; 2],
+            window_size: [1; 2],
+        };
+
+        let title = &self.title;
+        let text = &self.text;
+        let cache = &mut *ctx.cache;
+        ctx.graphics.draw(viewport, |context, graphics| {
+            graphics::clear(graphics::color::BLACK, graphics);
+            let width = f64::min(screen_width * 0.8, 400.0).floor();
+            let height = 20.0;
+            let x_pos = ((screen_width - width) / 2.0).floor();
+            let y_pos = (screen_height * 0.2 + height * 2.4).floor();
+
+            draw_text(
+                [x_pos, screen_height * 0.2, width, height * 1.5],
+                title,
+                (height * 1.5) as u32,
+                graphics::color::WHITE,
+                cache, &context, graphics,
+            );
+            graphics::Rectangle::new(graphics::color::WHITE).draw(
+                [x_pos, y_pos, width, height],
+                &context.draw_state,
+                context.transform,
+                graphics,
+            );
+            draw_text(
+                [x_pos, y_pos, width, height],
+                format!("{}_", text),
+                (height * 0.8) as u32,
+                graphics::color::BLACK,
+                cache, &context, graphics,
+            );
+        })
+    }
+}
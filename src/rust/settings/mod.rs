@@ -1,33 +1,241 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::thread;
 use std::sync::mpsc;
 
 use gtk::prelude::*;
 use gtk::{self, Orientation, PositionType, Window, WindowType};
 
+use bindings::Bindings;
+use config;
+use control;
 use mechanics::SettingsUpdate;
 use mechanics::PlayerSettings;
 
-macro_rules! add_slider {
-    ($container: expr, $channel: expr, $default: expr, $update: ident) => ({
-        add_slider!($container, $channel, $default, $update, 0.0, 1000.0, 10.0)
+/// File in the config dir which tuned physics settings are saved to.
+const SETTINGS_FILE: &'static str = "physics.conf";
+/// Preset for the default settings, which isn't loaded from a file.
+const DEFAULT_PRESET: &'static str = "default";
+
+/// Milliseconds between the settings window checking for telemetry.
+const TELEMETRY_POLL_INTERVAL: u32 = 100;
+
+/// What the game sends back to the settings window.
+#[derive(Clone, Debug)]
+pub enum Telemetry {
+    /// The player's state, along with frames drawn per second since the last report.
+    Player {
+        x: f64,
+        y: f64,
+        velocity_x: f64,
+        velocity_y: f64,
+        grounded: bool,
+        on_left_wall: bool,
+        on_right_wall: bool,
+        fps: f64,
+    },
+    /// A setting the game changed itself, such as from the options menu.
+    Setting(SettingsUpdate),
+    /// The current level's physics overrides. They only apply to that level, so they're shown
+    /// apart from the sliders, which keep the settings saved by the Save button.
+    Overrides(Vec<SettingsUpdate>),
+}
+
+/// Receives changes from the settings window, and remembers the latest value of everything so
+/// each new level starts with the tuned settings.
+pub struct SettingsChannel {
+    receiver: mpsc::Receiver<SettingsUpdate>,
+    /// Sends to `receiver`, for changes made from inside the game.
+    sender: mpsc::Sender<SettingsUpdate>,
+    telemetry: mpsc::Sender<Telemetry>,
+    /// Commands from the control socket, if it's enabled.
+    control: Option<mpsc::Receiver<control::Request>>,
+    current: PlayerSettings,
+    /// Not physics, but kept here since this is passed everywhere in the game.
+    bindings: Bindings,
+}
+
+impl SettingsChannel {
+    pub fn new(sender: mpsc::Sender<SettingsUpdate>, receiver: mpsc::Receiver<SettingsUpdate>,
+               telemetry: mpsc::Sender<Telemetry>, initial: PlayerSettings)
+               -> SettingsChannel {
+        SettingsChannel {
+            receiver: receiver,
+            sender: sender,
+            telemetry: telemetry,
+            control: None,
+            current: initial,
+            bindings: Bindings::default(),
+        }
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    pub fn listen(&mut self, requests: mpsc::Receiver<control::Request>) {
+        self.control = Some(requests);
+    }
+
+    /// Takes the control socket commands waiting to be answered.
+    pub fn control_requests(&mut self) -> Vec<control::Request> {
+        match self.control {
+            Some(ref requests) => requests.try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn report(&self, telemetry: Telemetry) {
+        // The settings window closing, or failing to open, shouldn't affect the game.
+        let _ = self.telemetry.send(telemetry);
+    }
+
+    /// Sends a change the same way the settings window does, and reports it back to the window so
+    /// its sliders follow.
+    pub fn send(&self, update: SettingsUpdate) {
+        if let Err(mpsc::SendError(v)) = self.sender.send(update) {
+            println!("Couldn't send value: {:?}", v);
+        }
+        self.report(Telemetry::Setting(update));
+    }
+
+    pub fn current(&self) -> &PlayerSettings {
+        &self.current
+    }
+
+    /// Takes all updates sent since the last poll, applying them to `current` as well.
+    pub fn poll(&mut self) -> Vec<SettingsUpdate> {
+        let updates = self.receiver.try_iter().collect::<Vec<_>>();
+        for &update in &updates {
+            self.current.apply(update);
+        }
+        updates
+    }
+}
+
+/// Loads the settings saved from the settings window, or the defaults if there aren't any.
+pub fn load_saved() -> PlayerSettings {
+    match config::read(SETTINGS_FILE) {
+        Ok(Some(text)) => parse_settings(&text, SETTINGS_FILE),
+        Ok(None) => PlayerSettings::default(),
+        Err(e) => {
+            println!("Failed to read {}, using default settings: {}", SETTINGS_FILE, e);
+            PlayerSettings::default()
+        }
+    }
+}
+
+/// Names and paths of the presets in the `physics` dir, sorted by name. The default settings
+/// come first as a preset without a file, so they can't drift from `PlayerSettings::default`.
+pub fn presets() -> Vec<(String, Option<PathBuf>)> {
+    let mut presets = vec![(DEFAULT_PRESET.to_owned(), None)];
+    let dir = match ::find_data_dir("physics") {
+        Some(dir) => dir,
+        None => return presets,
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Failed to list physics presets: {}", e);
+            return presets;
+        }
+    };
+    let mut files = entries.filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "conf"))
+        .filter_map(|path| {
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+            name.map(|name| (name, Some(path)))
+        })
+        .filter(|&(ref name, _)| name != DEFAULT_PRESET)
+        .collect::<Vec<_>>();
+    files.sort();
+    presets.extend(files);
+    presets
+}
+
+/// Loads a preset file. Settings it doesn't mention are left at their defaults.
+pub fn load_preset(path: &Path) -> io::Result<PlayerSettings> {
+    let mut text = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut text));
+    Ok(parse_settings(&text, &path.display().to_string()))
+}
+
+/// Parses `name: value` lines over the default settings, skipping any invalid ones.
+fn parse_settings(text: &str, source: &str) -> PlayerSettings {
+    let mut settings = PlayerSettings::default();
+    let pairs = match config::parse(text) {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            println!("Failed to load {}, using default settings: {}", source, e);
+            return settings;
+        }
+    };
+    for (key, value) in pairs {
+        match value.parse().ok().and_then(|value| SettingsUpdate::from_name(key, value)) {
+            Some(update) => settings.apply(update),
+            None => println!("Ignoring invalid setting in {}: {}: {}", source, key, value),
+        }
+    }
+    settings
+}
+
+pub fn save(settings: &PlayerSettings) -> io::Result<PathBuf> {
+    let text = settings.updates().iter()
+        .map(|update| format!("{}: {}\n", update.name(), update.value()))
+        .collect::<String>();
+    config::write(SETTINGS_FILE, &text)
+}
+
+/// Adds a slider for `default`'s setting, marked at its default value.
+fn add_slider(container: &gtk::Box, channel: &mpsc::Sender<SettingsUpdate>,
+              default: SettingsUpdate, (min, max, step): (f64, f64, f64))
+              -> (SettingsUpdate, gtk::Scale) {
+    let slider = gtk::Scale::new_with_range(Orientation::Horizontal, min, max, step);
+    let channel = channel.clone();
+    slider.add_mark(default.value(), PositionType::Bottom, Some(default.name()));
+    slider.set_value(default.value());
+    container.add(&slider);
+    slider.connect_change_value(move |_, _, value| {
+        if let Err(mpsc::SendError(v)) = channel.send(default.with_value(value)) {
+            println!("Couldn't send value: {:?}", v);
+        }
+        Inhibit(false)
     });
-    ($container: expr, $channel: expr, $default: expr, $update: ident,
-            $min: expr, $max: expr, $step: expr) => ({
-        let slider = gtk::Scale::new_with_range(Orientation::Horizontal, $min, $max, $step);
-        let channel = $channel.clone();
-        slider.add_mark($default, PositionType::Bottom, Some(stringify!($update)));
-        slider.set_value($default);
-        $container.add(&slider);
-        slider.connect_change_value(move |_, _, value| {
-            if let Err(mpsc::SendError(v)) = channel.send(SettingsUpdate::$update(value)) {
+    (default, slider)
+}
+
+/// Moves the sliders to show `settings`, and sends all of them to the game if `channel` is given.
+fn show_settings(sliders: &[(SettingsUpdate, gtk::Scale)],
+                 settings: &PlayerSettings, channel: Option<&mpsc::Sender<SettingsUpdate>>) {
+    for update in settings.updates() {
+        show_setting(sliders, update);
+        if let Some(channel) = channel {
+            if let Err(mpsc::SendError(v)) = channel.send(update) {
                 println!("Couldn't send value: {:?}", v);
             }
-            Inhibit(false)
-        });
-    })
+        }
+    }
 }
 
-fn run(channel: mpsc::Sender<SettingsUpdate>) {
+/// Moves the slider for one setting, if it has one.
+fn show_setting(sliders: &[(SettingsUpdate, gtk::Scale)], update: SettingsUpdate) {
+    for &(slider_update, ref slider) in sliders {
+        if slider_update.name() == update.name() {
+            slider.set_value(update.value());
+        }
+    }
+}
+
+fn run(channel: mpsc::Sender<SettingsUpdate>, telemetry: mpsc::Receiver<Telemetry>,
+       initial: Vec<SettingsUpdate>) {
     if let Err(e) = gtk::init() {
         println!("Failed to initialize GTK: {:?}", e);
         return;
@@ -43,17 +251,128 @@ fn run(channel: mpsc::Sender<SettingsUpdate>) {
     window.add(&scrolled);
     scrolled.add(&container);
 
-    let default = PlayerSettings::default();
-
-    add_slider!(container, channel, default.weight, Weight, 0.0, 10.0, 0.1);
-    add_slider!(container, channel, default.input_force, InputForce);
-    add_slider!(container, channel, default.jump_boost, JumpBoost);
-    add_slider!(container, channel, default.wall_boost_x, WallBoostX);
-    add_slider!(container, channel, default.wall_boost_y, WallBoostY);
-    add_slider!(container, channel, default.gravity_force, GravityForce);
-    add_slider!(container, channel, default.drag_constant, DragConstant, 0.0, 1.0, 0.01);
-    add_slider!(container, channel, default.tick_constant, TickConstant, 0.0, 20.0, 0.1);
-    // add_slider!(container, channel, default.jump_duration, JumpDuration);
+    let preset_box = gtk::ComboBoxText::new();
+    let preset_paths = presets();
+    for &(ref name, _) in &preset_paths {
+        preset_box.append_text(name);
+    }
+    container.add(&preset_box);
+
+    let position_label = gtk::Label::new(Some("Position: -"));
+    let velocity_label = gtk::Label::new(Some("Velocity: -"));
+    let contact_label = gtk::Label::new(Some("Touching: -"));
+    let fps_label = gtk::Label::new(Some("FPS: -"));
+    let overrides_label = gtk::Label::new(Some("Level overrides: none"));
+    container.add(&position_label);
+    container.add(&velocity_label);
+    container.add(&contact_label);
+    container.add(&fps_label);
+    container.add(&overrides_label);
+
+    let sliders = PlayerSettings::default().updates().into_iter().filter_map(|default| {
+        default.range().map(|range| add_slider(&container, &channel, default, range))
+    }).collect::<Vec<_>>();
+    // Sliders start at the defaults, then move to what the game actually started with.
+    let mut initial_settings = PlayerSettings::default();
+    for update in initial {
+        initial_settings.apply(update);
+    }
+    show_settings(&sliders, &initial_settings, None);
+    // Everything the game is using, including settings without sliders, so saving doesn't reset
+    // those to their defaults.
+    let loaded = Rc::new(RefCell::new(initial_settings));
+
+    let buttons = gtk::Box::new(Orientation::Horizontal, 10);
+    let save_button = gtk::Button::new_with_label("Save");
+    let reset_button = gtk::Button::new_with_label("Reset to defaults");
+    buttons.add(&save_button);
+    buttons.add(&reset_button);
+    container.add(&buttons);
+
+    {
+        let sliders = sliders.clone();
+        let loaded = loaded.clone();
+        save_button.connect_clicked(move |_| {
+            let mut settings = loaded.borrow().clone();
+            for &(update, ref slider) in &sliders {
+                settings.apply(update.with_value(slider.get_value()));
+            }
+            match save(&settings) {
+                Ok(path) => println!("Saved settings to {}", path.display()),
+                Err(e) => println!("Failed to save settings: {}", e),
+            }
+        });
+    }
+    {
+        let sliders = sliders.clone();
+        let loaded = loaded.clone();
+        gtk::timeout_add(TELEMETRY_POLL_INTERVAL, move || {
+            for message in telemetry.try_iter() {
+                match message {
+                    Telemetry::Player { x, y, velocity_x, velocity_y, grounded, on_left_wall,
+                                        on_right_wall, fps } => {
+                        let touching = [(grounded, "ground"),
+                                        (on_left_wall, "left wall"),
+                                        (on_right_wall, "right wall")]
+                            .iter()
+                            .filter(|&&(touching, _)| touching)
+                            .map(|&(_, name)| name)
+                            .collect::<Vec<_>>();
+                        position_label.set_text(&format!("Position: {:.1}, {:.1}", x, y));
+                        velocity_label.set_text(
+                            &format!("Velocity: {:.1}, {:.1}", velocity_x, velocity_y));
+                        contact_label.set_text(&format!("Touching: {}", touching.join(", ")));
+                        fps_label.set_text(&format!("FPS: {:.0}", fps));
+                    }
+                    Telemetry::Setting(update) => {
+                        show_setting(&sliders, update);
+                        loaded.borrow_mut().apply(update);
+                    }
+                    Telemetry::Overrides(ref updates) if updates.is_empty() => {
+                        overrides_label.set_text("Level overrides: none");
+                    }
+                    Telemetry::Overrides(updates) => {
+                        let overrides = updates.iter()
+                            .map(|update| format!("{}={}", update.name(), update.value()))
+                            .collect::<Vec<_>>();
+                        overrides_label.set_text(
+                            &format!("Level overrides: {}", overrides.join(", ")));
+                    }
+                }
+            }
+            gtk::Continue(true)
+        });
+    }
+    {
+        let sliders = sliders.clone();
+        let channel = channel.clone();
+        let loaded = loaded.clone();
+        reset_button.connect_clicked(move |_| {
+            show_settings(&sliders, &PlayerSettings::default(), Some(&channel));
+            *loaded.borrow_mut() = PlayerSettings::default();
+        });
+    }
+    preset_box.connect_changed(move |preset_box| {
+        let name = match preset_box.get_active_text() {
+            Some(name) => name,
+            None => return,
+        };
+        for &(ref preset_name, ref path) in &preset_paths {
+            if *preset_name == name {
+                let preset = match *path {
+                    Some(ref path) => load_preset(path),
+                    None => Ok(PlayerSettings::default()),
+                };
+                match preset {
+                    Ok(settings) => {
+                        show_settings(&sliders, &settings, Some(&channel));
+                        *loaded.borrow_mut() = settings;
+                    }
+                    Err(e) => println!("Failed to load preset {}: {}", name, e),
+                }
+            }
+        }
+    });
     window.show_all();
 
     window.connect_delete_event(|_, _| {
@@ -64,6 +383,46 @@ fn run(channel: mpsc::Sender<SettingsUpdate>) {
     gtk::main();
 }
 
-pub fn exec_threaded(channel: mpsc::Sender<SettingsUpdate>) {
-    thread::spawn(|| run(channel));
+pub fn exec_threaded(channel: mpsc::Sender<SettingsUpdate>, telemetry: mpsc::Receiver<Telemetry>,
+                     initial: Vec<SettingsUpdate>) {
+    thread::spawn(|| run(channel, telemetry, initial));
+}
+
+#[cfg(test)]
+mod tests {
+    use mechanics::PlayerSettings;
+
+    use super::parse_settings;
+
+    /// Every setting which differs from the defaults, by name.
+    fn changed(settings: &PlayerSettings) -> Vec<(&'static str, f64)> {
+        settings.updates().into_iter()
+            .zip(PlayerSettings::default().updates())
+            .filter(|&(update, default)| update.value() != default.value())
+            .map(|(update, _)| (update.name(), update.value()))
+            .collect()
+    }
+
+    #[test]
+    fn partial_presets_keep_other_defaults() {
+        let ice = parse_settings(include_str!("../../../physics/ice.conf"), "ice.conf");
+        assert_eq!(changed(&ice), vec![("input_force", 150.0), ("drag_constant", 0.02)]);
+
+        let moon = parse_settings(include_str!("../../../physics/moon.conf"), "moon.conf");
+        assert_eq!(changed(&moon), vec![("jump_boost", 300.0),
+                                        ("wall_boost_y", 180.0),
+                                        ("gravity_force", 110.0),
+                                        ("drag_constant", 0.04)]);
+    }
+
+    #[test]
+    fn skips_invalid_settings() {
+        let settings = parse_settings("weight: 5\nnot_a_setting: 3\njump_boost: high\n", "test");
+        assert_eq!(changed(&settings), vec![("weight", 5.0)]);
+    }
+
+    #[test]
+    fn empty_preset_is_defaults() {
+        assert!(changed(&parse_settings("# nothing here\n", "test")).is_empty());
+    }
 }
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::str::{self, FromStr};
+
+use image;
+use opengl_graphics::Texture as OpenGlTexture;
+use opengl_graphics::TextureSettings;
+
+/// Files compiled into the binary, used when the `assets` directory can't be found or contains
+/// a broken manifest. Image paths are exactly as written in the embedded manifests.
+static EMBEDDED_FILES: &'static [(&'static str, &'static [u8])] = &[
+    ("player.manifest", &include_bytes!("../../../assets/player.manifest")[..]),
+    ("patroller.manifest", &include_bytes!("../../../assets/patroller.manifest")[..]),
+    ("../src/png/unarmed/readyleft.png", &include_bytes!("../../png/unarmed/readyleft.png")[..]),
+    ("../src/png/unarmed/readyright.png", &include_bytes!("../../png/unarmed/readyright.png")[..]),
+    ("../src/png/unarmed/runleft.png", &include_bytes!("../../png/unarmed/runleft.png")[..]),
+    ("../src/png/unarmed/runright.png", &include_bytes!("../../png/unarmed/runright.png")[..]),
+    ("../src/png/unarmed/painleft.png", &include_bytes!("../../png/unarmed/painleft.png")[..]),
+    ("../src/png/unarmed/painright.png", &include_bytes!("../../png/unarmed/painright.png")[..]),
+    ("../src/png/unarmed/fallenleft.png", &include_bytes!("../../png/unarmed/fallenleft.png")[..]),
+    ("../src/png/unarmed/fallenright.png",
+     &include_bytes!("../../png/unarmed/fallenright.png")[..]),
+    ("../src/png/unarmed/advanceleft.png",
+     &include_bytes!("../../png/unarmed/advanceleft.png")[..]),
+    ("../src/png/unarmed/advanceright.png",
+     &include_bytes!("../../png/unarmed/advanceright.png")[..]),
+    ("../src/png/unarmed/attackleft.png", &include_bytes!("../../png/unarmed/attackleft.png")[..]),
+    ("../src/png/unarmed/attackright.png",
+     &include_bytes!("../../png/unarmed/attackright.png")[..]),
+];
+
+#[derive(Debug)]
+pub enum SpriteError {
+    Io(PathBuf, io::Error),
+    NotEmbedded(String),
+    NotUtf8(String),
+    Syntax { line: usize, message: String },
+    MissingCollisionBox,
+    MissingAnimation(String),
+    Image(String, image::ImageError),
+    BadFrameCount { animation: String, image_width: u32, frames: u32 },
+}
+
+impl fmt::Display for SpriteError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SpriteError::Io(ref path, ref e) => write!(fmt, "{}: {}", path.display(), e),
+            SpriteError::NotEmbedded(ref file) => write!(fmt, "{} is not a built-in file", file),
+            SpriteError::NotUtf8(ref file) => write!(fmt, "{} is not valid UTF-8", file),
+            SpriteError::Syntax { line, ref message } => write!(fmt, "line {}: {}", line, message),
+            SpriteError::MissingCollisionBox => write!(fmt, "manifest has no `collision` line"),
+            SpriteError::MissingAnimation(ref name) => {
+                write!(fmt, "manifest has no `animation.{}` line", name)
+            }
+            SpriteError::Image(ref file, ref e) => write!(fmt, "{}: {}", file, e),
+            SpriteError::BadFrameCount { ref animation, image_width, frames } => {
+                write!(fmt, "animation {}: image width {} can't be split into {} frames",
+                       animation, image_width, frames)
+            }
+        }
+    }
+}
+
+/// A single frame of an animation, along with where to draw it relative to the entity.
+pub struct Frame<'a> {
+    pub texture: &'a OpenGlTexture,
+    pub x_offset: f64,
+    pub y_offset: f64,
+}
+
+pub struct Animation {
+    frames: Vec<OpenGlTexture>,
+    /// seconds, 0 for still images
+    frame_duration: f64,
+    x_offset: f64,
+    y_offset: f64,
+    reversed: bool,
+}
+
+impl Animation {
+    /// Gets the frame shown `time` seconds into the animation, looping.
+    pub fn frame(&self, time: f64) -> Frame {
+        if self.frame_duration <= 0.0 {
+            return self.first();
+        }
+        let index = (time / self.frame_duration) as usize % self.frames.len();
+        let index = if self.reversed { self.frames.len() - 1 - index } else { index };
+        self.make_frame(index)
+    }
+
+    pub fn first(&self) -> Frame {
+        self.make_frame(0)
+    }
+
+    fn make_frame(&self, index: usize) -> Frame {
+        Frame {
+            texture: &self.frames[index],
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+        }
+    }
+}
+
+pub struct SpriteSheet {
+    animations: HashMap<String, Animation>,
+    /// width, height
+    collision_box: (u32, u32),
+}
+
+impl SpriteSheet {
+    /// Removes the named animation from this sheet, for storing in a typed graphics struct.
+    pub fn take(&mut self, name: &str) -> Result<Animation, SpriteError> {
+        self.animations.remove(name).ok_or_else(|| SpriteError::MissingAnimation(name.to_owned()))
+    }
+
+    /// width, height
+    pub fn collision_box(&self) -> (u32, u32) {
+        self.collision_box
+    }
+
+    fn from_source(source: &Source, file: &str, names: &[&str])
+                   -> Result<SpriteSheet, SpriteError> {
+        let bytes = try!(source.read(file));
+        let text = try!(str::from_utf8(&bytes).map_err(|_| SpriteError::NotUtf8(file.to_owned())));
+        let manifest = try!(parse_manifest(text, names));
+
+        let mut animations = HashMap::new();
+        for (name, spec) in manifest.animations {
+            let image = try!(source.read(&spec.file));
+            let frames = try!(load_texture_frames(&name, &spec.file, &image, spec.frames));
+            animations.insert(name, Animation {
+                frames: frames,
+                frame_duration: spec.frame_duration,
+                x_offset: spec.x_offset,
+                y_offset: spec.y_offset,
+                reversed: spec.reversed,
+            });
+        }
+
+        Ok(SpriteSheet {
+            animations: animations,
+            collision_box: manifest.collision_box,
+        })
+    }
+}
+
+/// Loads `<name>.manifest` from the `assets` directory and hands it to `build`. The manifest must
+/// have exactly the animations in `names`. If the directory can't be found or the manifest fails
+/// to load or validate, the built-in copy is used instead.
+pub fn load<T, F>(name: &str, names: &[&str], build: F) -> T
+    where F: Fn(SpriteSheet) -> Result<T, SpriteError>
+{
+    let file = format!("{}.manifest", name);
+    if let Some(dir) = ::find_data_dir("assets") {
+        match SpriteSheet::from_source(&Source::Directory(dir), &file, names).and_then(&build) {
+            Ok(v) => return v,
+            Err(e) => println!("Failed to load {}, using built-in sprites: {}", file, e),
+        }
+    }
+    match SpriteSheet::from_source(&Source::Embedded, &file, names).and_then(&build) {
+        Ok(v) => v,
+        Err(e) => panic!("Built-in sprite manifest {} is invalid: {}", file, e),
+    }
+}
+
+enum Source {
+    Directory(PathBuf),
+    Embedded,
+}
+
+impl Source {
+    fn read(&self, file: &str) -> Result<Vec<u8>, SpriteError> {
+        match *self {
+            Source::Directory(ref dir) => {
+                let path = dir.join(file);
+                let mut buf = Vec::new();
+                try!(File::open(&path)
+                    .and_then(|mut f| f.read_to_end(&mut buf))
+                    .map_err(|e| SpriteError::Io(path.clone(), e)));
+                Ok(buf)
+            }
+            Source::Embedded => {
+                EMBEDDED_FILES.iter()
+                    .find(|&&(name, _)| name == file)
+                    .map(|&(_, bytes)| bytes.to_vec())
+                    .ok_or_else(|| SpriteError::NotEmbedded(file.to_owned()))
+            }
+        }
+    }
+}
+
+struct AnimationSpec {
+    file: String,
+    frames: u32,
+    frame_duration: f64,
+    x_offset: f64,
+    y_offset: f64,
+    reversed: bool,
+}
+
+struct Manifest {
+    collision_box: (u32, u32),
+    animations: Vec<(String, AnimationSpec)>,
+}
+
+/// Parses a manifest, which must have an animation for each of `names` and no others.
+fn parse_manifest(text: &str, names: &[&str]) -> Result<Manifest, SpriteError> {
+    let mut collision_box = None;
+    let mut animations = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let syntax_error = |message: String| {
+            SpriteError::Syntax { line: line_number, message: message }
+        };
+
+        let (key, value) = match line.find(':') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => return Err(syntax_error("expected `key: value`".to_owned())),
+        };
+        let fields = value.split(',').map(str::trim).collect::<Vec<_>>();
+
+        if key == "collision" {
+            if fields.len() != 2 {
+                return Err(syntax_error("expected `collision: width,height`".to_owned()));
+            }
+            collision_box = Some((try!(parse_field(fields[0], line_number)),
+                                  try!(parse_field(fields[1], line_number))));
+        } else if key.starts_with("animation.") {
+            if fields.len() < 5 || fields.len() > 6 {
+                return Err(syntax_error("expected `animation.<name>: file,frames,\
+                                         frame_duration,x_offset,y_offset[,reversed]`"
+                    .to_owned()));
+            }
+            let reversed = match fields.get(5) {
+                None => false,
+                Some(&"reversed") => true,
+                Some(other) => return Err(syntax_error(format!("unknown flag `{}`", other))),
+            };
+            let name = &key["animation.".len()..];
+            if !names.contains(&name) {
+                return Err(syntax_error(format!("unknown animation `{}`", name)));
+            }
+            animations.push((name.to_owned(), AnimationSpec {
+                file: fields[0].to_owned(),
+                frames: try!(parse_field(fields[1], line_number)),
+                frame_duration: try!(parse_field(fields[2], line_number)),
+                x_offset: try!(parse_field(fields[3], line_number)),
+                y_offset: try!(parse_field(fields[4], line_number)),
+                reversed: reversed,
+            }));
+        } else {
+            return Err(syntax_error(format!("unknown key `{}`", key)));
+        }
+    }
+
+    for &name in names {
+        if !animations.iter().any(|&(ref found, _)| found == name) {
+            return Err(SpriteError::MissingAnimation(name.to_owned()));
+        }
+    }
+
+    Ok(Manifest {
+        collision_box: try!(collision_box.ok_or(SpriteError::MissingCollisionBox)),
+        animations: animations,
+    })
+}
+
+fn parse_field<T: FromStr>(field: &str, line: usize) -> Result<T, SpriteError> {
+    field.parse().map_err(|_| {
+        SpriteError::Syntax {
+            line: line,
+            message: format!("`{}` is not a valid number", field),
+        }
+    })
+}
+
+fn load_texture_frames(animation: &str, file: &str, bytes: &[u8], num_frames: u32)
+                       -> Result<Vec<OpenGlTexture>, SpriteError> {
+    let mut image = try!(image::load_from_memory_with_format(bytes, image::ImageFormat::PNG)
+            .map_err(|e| SpriteError::Image(file.to_owned(), e)))
+        .to_rgba();
+    let (image_width, height) = image.dimensions();
+
+    if num_frames == 0 || image_width % num_frames != 0 {
+        return Err(SpriteError::BadFrameCount {
+            animation: animation.to_owned(),
+            image_width: image_width,
+            frames: num_frames,
+        });
+    }
+
+    let width = image_width / num_frames;
+
+    Ok((0..num_frames).map(|x| {
+        let sub_image = image::SubImage::new(&mut image, x * width, 0, width, height);
+
+        OpenGlTexture::from_image(&sub_image.to_image(), &TextureSettings::new())
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpriteError, parse_manifest};
+
+    const NAMES: &'static [&'static str] = &["left", "right"];
+
+    #[test]
+    fn parses_manifest() {
+        let manifest = parse_manifest("# comment\n\
+                                       collision: 10,20\n\
+                                       animation.left: left.png,6,0.08,-11,0,reversed\n\
+                                       animation.right: right.png,1,0,-11,0\n",
+                                      NAMES)
+            .unwrap();
+        assert_eq!(manifest.collision_box, (10, 20));
+        assert_eq!(manifest.animations.len(), 2);
+        let (ref name, ref left) = manifest.animations[0];
+        assert_eq!(name, "left");
+        assert_eq!(left.file, "left.png");
+        assert_eq!(left.frames, 6);
+        assert!(left.reversed);
+        assert!(!manifest.animations[1].1.reversed);
+    }
+
+    #[test]
+    fn rejects_missing_frames() {
+        let result = parse_manifest("collision: 10,20\n\
+                                     animation.left: left.png,0.08,-11,0\n\
+                                     animation.right: right.png,1,0,-11,0\n",
+                                    NAMES);
+        match result {
+            Err(SpriteError::Syntax { line: 2, .. }) => (),
+            _ => panic!("expected a syntax error on line 2"),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_collision_line() {
+        let result = parse_manifest("collision: 10\n\
+                                     animation.left: left.png,1,0,-11,0\n\
+                                     animation.right: right.png,1,0,-11,0\n",
+                                    NAMES);
+        match result {
+            Err(SpriteError::Syntax { line: 1, .. }) => (),
+            _ => panic!("expected a syntax error on line 1"),
+        }
+        let result = parse_manifest("collision: 10,tall\n\
+                                     animation.left: left.png,1,0,-11,0\n\
+                                     animation.right: right.png,1,0,-11,0\n",
+                                    NAMES);
+        match result {
+            Err(SpriteError::Syntax { line: 1, .. }) => (),
+            _ => panic!("expected a syntax error on line 1"),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_animation() {
+        let result = parse_manifest("collision: 10,20\n\
+                                     animation.left: left.png,1,0,-11,0\n\
+                                     animation.right: right.png,1,0,-11,0\n\
+                                     animation.up: up.png,1,0,-11,0\n",
+                                    NAMES);
+        match result {
+            Err(SpriteError::Syntax { line: 4, .. }) => (),
+            _ => panic!("expected a syntax error on line 4"),
+        }
+    }
+
+    #[test]
+    fn rejects_missing_animation() {
+        let result = parse_manifest("collision: 10,20\n\
+                                     animation.left: left.png,1,0,-11,0\n",
+                                    NAMES);
+        match result {
+            Err(SpriteError::MissingAnimation(ref name)) if name == "right" => (),
+            _ => panic!("expected `right` to be missing"),
+        }
+    }
+}